@@ -0,0 +1,84 @@
+//! Compares the heap allocating `ValueBuilder` with the arena backed
+//! `ArenaBuilder`: `cargo bench --features arena`.
+use bumpalo::Bump;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use parity_scale_codec::Encode;
+use scale_borrow::arena::ArenaBuilder;
+use scale_borrow::ValueBuilder;
+
+#[derive(Encode, scale_info::TypeInfo)]
+enum Event {
+    Transfer {
+        from: [u8; 32],
+        to: [u8; 32],
+        #[codec(compact)]
+        amount: u128,
+    },
+    Deposit {
+        who: [u8; 32],
+        amount: u128,
+    },
+    Remark(String),
+}
+
+#[derive(Encode, scale_info::TypeInfo)]
+struct Record {
+    phase: u32,
+    event: Event,
+    topics: Vec<u64>,
+}
+
+#[derive(Encode, scale_info::TypeInfo)]
+struct Block {
+    number: u64,
+    records: Vec<Record>,
+}
+
+fn block() -> Block {
+    let records = (0..10)
+        .map(|i| Record {
+            phase: i,
+            event: match i % 3 {
+                0 => Event::Transfer {
+                    from: [i as u8; 32],
+                    to: [0xff; 32],
+                    amount: 1 << 100,
+                },
+                1 => Event::Deposit {
+                    who: [i as u8; 32],
+                    amount: 12_345,
+                },
+                _ => Event::Remark("the quick brown fox".into()),
+            },
+            topics: (0..8).collect(),
+        })
+        .collect();
+    Block {
+        number: 1_000_000,
+        records,
+    }
+}
+
+fn builders(c: &mut Criterion) {
+    let encoded = block().encode();
+    let m = scale_info::MetaType::new::<Block>();
+    let mut types = scale_info::Registry::new();
+    let id = types.register_type(&m).id();
+    let types: scale_info::PortableRegistry = types.into();
+
+    c.bench_function("ValueBuilder::parse", |b| {
+        b.iter(|| ValueBuilder::parse(black_box(&encoded), id, &types))
+    });
+
+    let mut bump = Bump::new();
+    c.bench_function("ArenaBuilder::parse", |b| {
+        b.iter(|| {
+            bump.reset();
+            let val = ArenaBuilder::parse(black_box(&encoded), id, &types, &bump);
+            black_box(val.type_id());
+        })
+    });
+}
+
+criterion_group!(benches, builders);
+criterion_main!(benches);
@@ -0,0 +1,132 @@
+//! Decoding a few paths out of a wide struct with `descale!`, bound and
+//! unbound, against matching every field's path one at a time:
+//! `cargo bench --bench descale`.
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use parity_scale_codec::Encode;
+use scale_borrow::descale::{self, Descale, ParseError};
+use scale_borrow::scale_info::form::PortableForm;
+use scale_borrow::scale_info::{PortableRegistry, Type};
+use scale_borrow::{skeleton_decode, PathSegment, VisitScale};
+
+#[derive(Encode, scale_info::TypeInfo)]
+struct Account {
+    nonce: u32,
+    free: u64,
+    reserved: u64,
+    frozen: u64,
+}
+
+#[derive(Encode, scale_info::TypeInfo)]
+struct Accounts {
+    alice: Account,
+    bob: Account,
+    charlie: Account,
+    dave: Account,
+    eve: Account,
+    ferdie: Account,
+    others: Vec<Account>,
+}
+
+scale_borrow::descale! {
+    struct Balances<'scale> {
+        #[path("alice.free")]
+        alice: u64,
+        #[path("bob.free")]
+        bob: u64,
+        #[path("charlie.free")]
+        charlie: u64,
+        #[path("dave.free")]
+        dave: u64,
+        #[path("eve.free")]
+        eve: u64,
+        #[path("ferdie.free")]
+        ferdie: u64,
+        #[path("alice.nonce")]
+        alice_nonce: u32,
+        #[path("others.*.free")]
+        others: Vec<u64>,
+    }
+}
+
+/// What `descale!` used to do: try each field's pattern in turn.
+#[derive(Default)]
+struct Linear<'scale>(Balances<'scale>);
+
+impl<'scale> Linear<'scale> {
+    fn visit_fields(
+        &mut self,
+        path: &[(PathSegment<'scale>, u32)],
+        data: &'scale [u8],
+        ty: &'scale Type<PortableForm>,
+        types: &'scale PortableRegistry,
+    ) -> Result<(), ParseError> {
+        let b = &mut self.0;
+        descale::visit_field(&mut b.alice, "alice.free", path, data, ty, types)?;
+        descale::visit_field(&mut b.bob, "bob.free", path, data, ty, types)?;
+        descale::visit_field(&mut b.charlie, "charlie.free", path, data, ty, types)?;
+        descale::visit_field(&mut b.dave, "dave.free", path, data, ty, types)?;
+        descale::visit_field(&mut b.eve, "eve.free", path, data, ty, types)?;
+        descale::visit_field(&mut b.ferdie, "ferdie.free", path, data, ty, types)?;
+        descale::visit_field(&mut b.alice_nonce, "alice.nonce", path, data, ty, types)?;
+        descale::visit_field(&mut b.others, "others.*.free", path, data, ty, types)
+    }
+}
+
+impl<'scale> VisitScale<'scale> for Linear<'scale> {
+    fn visit(
+        &mut self,
+        path: &[(PathSegment<'scale>, u32)],
+        data: &'scale [u8],
+        ty: &'scale Type<PortableForm>,
+        types: &'scale PortableRegistry,
+    ) {
+        self.visit_fields(path, data, ty, types)
+            .expect("no converters to fail");
+    }
+}
+
+fn accounts() -> Accounts {
+    let account = |i| Account {
+        nonce: i,
+        free: i as u64 * 1000,
+        reserved: 1,
+        frozen: 2,
+    };
+    Accounts {
+        alice: account(0),
+        bob: account(1),
+        charlie: account(2),
+        dave: account(3),
+        eve: account(4),
+        ferdie: account(5),
+        others: (6..100).map(account).collect(),
+    }
+}
+
+fn descale(c: &mut Criterion) {
+    let encoded = accounts().encode();
+    let m = scale_info::MetaType::new::<Accounts>();
+    let mut types = scale_info::Registry::new();
+    let id = types.register_type(&m).id();
+    let types: PortableRegistry = types.into();
+
+    let bound = Balances::bind(id, &types).unwrap();
+    c.bench_function("descale! matches", |b| {
+        b.iter(|| black_box(bound.parse(black_box(&encoded))))
+    });
+
+    c.bench_function("descale! unbound", |b| {
+        b.iter(|| black_box(Balances::parse(black_box(&encoded), id, &types)))
+    });
+
+    c.bench_function("descale linear", |b| {
+        b.iter(|| {
+            let mut linear = Linear::default();
+            skeleton_decode(black_box(&encoded), id, &mut linear, &types);
+            black_box(linear.0)
+        })
+    });
+}
+
+criterion_group!(benches, descale);
+criterion_main!(benches);
@@ -0,0 +1,46 @@
+//! Path lookups into a wide object, with and without an `IndexedValue`:
+//! `cargo bench --bench lookup`.
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use scale_borrow::Value;
+use std::borrow::Cow;
+
+fn accounts(width: u32) -> Value<'static> {
+    let mut fields = vec![("_ty".into(), Value::U32(0))];
+    for i in 0..width {
+        let account = Value::Object(Box::new(vec![
+            ("_ty".into(), Value::U32(1)),
+            ("nonce".into(), Value::U32(i)),
+            ("free".into(), Value::U128(Box::new(i as u128))),
+        ]));
+        fields.push((Cow::Owned(format!("acc{}", i)), account));
+    }
+    Value::Object(Box::new(fields))
+}
+
+fn lookup(c: &mut Criterion) {
+    let val = accounts(1000);
+    let paths: Vec<String> = (0..1000)
+        .step_by(37)
+        .map(|i| format!("acc{}.free", i))
+        .collect();
+
+    c.bench_function("Value::get", |b| {
+        b.iter(|| {
+            for path in &paths {
+                black_box(val.get(black_box(path)));
+            }
+        })
+    });
+
+    let index = val.indexed();
+    c.bench_function("IndexedValue::get", |b| {
+        b.iter(|| {
+            for path in &paths {
+                black_box(index.get(black_box(path)));
+            }
+        })
+    });
+}
+
+criterion_group!(benches, lookup);
+criterion_main!(benches);
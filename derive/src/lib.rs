@@ -0,0 +1,1058 @@
+//! `#[derive(Descale)]`, re-exported by `scale-borrow` with its `derive`
+//! feature.
+//!
+//! ```ignore
+//! #[derive(Descale)]
+//! pub struct Transfer<'scale> {
+//!     /// Who sent it.
+//!     #[descale(path = "Balances.transfer.dest.Id")]
+//!     pub dest: &'scale [u8],
+//!     #[descale(path = "Balances.transfer.value")]
+//!     pub value: u128,
+//! }
+//!
+//! let transfer = Transfer::parse(&call_data, call_ty, &registry);
+//! ```
+//!
+//! This generates the same `parse` function and `VisitScale` and `Descale`
+//! impls as the `descale!` macro but works on ordinary struct definitions, so
+//! visibility, doc comments, other derives and generics are all kept. Fields
+//! without a `#[descale(path = ..)]` are left at their `Default`.
+//!
+//! `#[descale(path = "..", with = my_fn)]` sets the field to `my_fn` of
+//! the value at the path instead, and `try_with` takes a function returning a
+//! `Result` whose error fails `try_parse`. These take a function's path, or
+//! a closure in parentheses: `with = (|v: u8| v * 2)`.
+//!
+//! The paths are compiled into nested `match`es on the walker's path
+//! segments, so a visit costs one string match per segment however many
+//! fields there are.
+//!
+//! The attribute is `#[descale(path = "..")]` rather than `descale!`'s
+//! `#[path("..")]` because `path` is a built-in attribute.
+//!
+//! A `*` segment in a path matches anything; give the field a collection
+//! type such as `Vec<&'scale str>` to keep every match.
+//!
+//! A field can be another `Descale` struct (which must also implement
+//! `Default`) mounted at its path, with its own paths followed from there.
+//!
+//! A field is left at its default when its path isn't in the data, e.g.
+//! because it goes through another enum variant. Use an `Option` field to
+//! tell that apart from a zero, or `#[descale(path = "..", required)]` to make
+//! parsing fail.
+//!
+//! On an enum, each arm is matched to the variant of the same name, or the
+//! one given by `#[descale(variant = "..")]`, and its fields' paths start
+//! inside that variant. One arm must be marked `#[descale(other)]` and have
+//! two fields, which get the name and bytes of any other variant.
+//!
+//! Generated code refers to `::scale_borrow`; if the crate has been renamed
+//! say so with `#[descale(crate = "my_scale_borrow")]` on the struct. A bare
+//! path is taken too, which is how `descale!` passes `$crate`.
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2, TokenTree};
+use quote::{format_ident, quote, quote_spanned, ToTokens};
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, parse_quote, parse_quote_spanned, Data, DeriveInput, Lifetime, LitStr,
+    Member,
+};
+
+#[proc_macro_derive(Descale, attributes(descale))]
+pub fn derive_descale(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Push `err` onto the errors found so far.
+fn combine(errors: &mut Option<syn::Error>, err: syn::Error) {
+    match errors {
+        Some(errors) => errors.combine(err),
+        None => *errors = Some(err),
+    }
+}
+
+/// Options from `#[descale(..)]` on the struct.
+struct Container {
+    krate: syn::Path,
+}
+
+impl Container {
+    fn from_attrs(attrs: &[syn::Attribute]) -> syn::Result<Self> {
+        let mut krate = None;
+        for attr in attrs.iter().filter(|a| a.path().is_ident("descale")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("crate") {
+                    if krate.is_some() {
+                        return Err(meta.error("duplicate `crate`"));
+                    }
+                    let value = meta.value()?;
+                    krate = Some(match value.peek(LitStr) {
+                        true => value.parse::<LitStr>()?.parse()?,
+                        false => value.parse()?,
+                    });
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown descale option, expected `crate`"))
+                }
+            })?;
+        }
+        Ok(Container {
+            krate: krate.unwrap_or_else(|| parse_quote!(::scale_borrow)),
+        })
+    }
+}
+
+/// A field and the path it's decoded from, if it has one.
+struct DescaleField {
+    member: Member,
+    ty: syn::Type,
+    path: Option<Vec<String>>,
+    /// Parsing fails if the path isn't visited.
+    required: Option<Span>,
+    /// `with` or `try_with`, and the function the field is set with: a path
+    /// or a parenthesized closure.
+    convert: Option<(syn::Ident, TokenStream2)>,
+}
+
+impl DescaleField {
+    fn new(index: usize, field: &syn::Field) -> syn::Result<Self> {
+        let mut path = None;
+        let mut required = None;
+        let mut convert: Option<(syn::Ident, TokenStream2)> = None;
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("descale")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("path") {
+                    if path.is_some() {
+                        return Err(meta.error("duplicate `path`"));
+                    }
+                    let lit: LitStr = meta.value()?.parse()?;
+                    path = Some(split_path(&lit)?);
+                    Ok(())
+                } else if meta.path.is_ident("required") {
+                    if required.is_some() {
+                        return Err(meta.error("duplicate `required`"));
+                    }
+                    required = Some(meta.path.span());
+                    Ok(())
+                } else if meta.path.is_ident("with") || meta.path.is_ident("try_with") {
+                    if convert.is_some() {
+                        return Err(meta.error("only one of `with` and `try_with` can be given"));
+                    }
+                    let ident = meta.path.get_ident().unwrap().clone();
+                    let value = meta.value()?;
+                    let with = match value.peek(syn::token::Paren) {
+                        true => value.parse::<TokenTree>()?.into(),
+                        false => value.parse::<syn::Path>()?.into_token_stream(),
+                    };
+                    convert = Some((ident, with));
+                    Ok(())
+                } else {
+                    Err(meta.error(
+                        "unknown descale field option, expected `path`, `required`, `with` or `try_with`",
+                    ))
+                }
+            })?;
+        }
+        if let (Some(span), None) = (required, &path) {
+            return Err(syn::Error::new(span, "`required` needs a `path`"));
+        }
+        if let (Some((ident, _)), None) = (&convert, &path) {
+            return Err(syn::Error::new(
+                ident.span(),
+                format!("`{}` needs a `path`", ident),
+            ));
+        }
+        let member = match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(index.into()),
+        };
+        Ok(DescaleField {
+            member,
+            ty: field.ty.clone(),
+            path,
+            required,
+            convert,
+        })
+    }
+}
+
+/// Whether `tokens` use any of the identifiers in `params`.
+fn mentions(tokens: TokenStream2, params: &[&syn::Ident]) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(ident) => params.contains(&&ident),
+        TokenTree::Group(group) => mentions(group.stream(), params),
+        _ => false,
+    })
+}
+
+/// The segments of a dotted path such as `"outer.0.val"`.
+fn split_path(lit: &LitStr) -> syn::Result<Vec<String>> {
+    let path = lit.value();
+    if path.is_empty() {
+        return Err(syn::Error::new(lit.span(), "path must not be empty"));
+    }
+    let segments: Vec<String> = path.split('.').map(String::from).collect();
+    if segments.iter().any(String::is_empty) {
+        return Err(syn::Error::new(
+            lit.span(),
+            format!(
+                "empty segment in path {:?}; segments are separated by a single `.`",
+                path
+            ),
+        ));
+    }
+    Ok(segments)
+}
+
+/// An enum arm matched by variant name.
+struct Arm {
+    ident: syn::Ident,
+    /// The variant name in the metadata.
+    variant: String,
+    fields: Vec<DescaleField>,
+}
+
+/// The `#[descale(other)]` arm, with the members for the variant name and
+/// its bytes.
+struct Other {
+    ident: syn::Ident,
+    name: Member,
+    data: Member,
+}
+
+enum Body {
+    Struct(Vec<DescaleField>),
+    Enum { arms: Vec<Arm>, other: Other },
+}
+
+fn struct_body(fields: &syn::Fields, errors: &mut Option<syn::Error>) -> Body {
+    let mut descale_fields = vec![];
+    for (i, field) in fields.iter().enumerate() {
+        match DescaleField::new(i, field) {
+            Ok(field) => descale_fields.push(field),
+            Err(err) => combine(errors, err),
+        }
+    }
+    Body::Struct(descale_fields)
+}
+
+fn enum_body(
+    name: &syn::Ident,
+    data: &syn::DataEnum,
+    errors: &mut Option<syn::Error>,
+) -> syn::Result<Body> {
+    let mut arms = vec![];
+    let mut other = None;
+    for variant in &data.variants {
+        let mut name = None;
+        let mut is_other = None;
+        for attr in variant
+            .attrs
+            .iter()
+            .filter(|a| a.path().is_ident("descale"))
+        {
+            let parsed = attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("variant") {
+                    if name.is_some() {
+                        return Err(meta.error("duplicate `variant`"));
+                    }
+                    let lit: LitStr = meta.value()?.parse()?;
+                    name = Some(lit.value());
+                    Ok(())
+                } else if meta.path.is_ident("other") {
+                    if is_other.is_some() {
+                        return Err(meta.error("duplicate `other`"));
+                    }
+                    is_other = Some(meta.path.span());
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown descale variant option, expected `variant` or `other`"))
+                }
+            });
+            if let Err(err) = parsed {
+                combine(errors, err);
+            }
+        }
+
+        if let Some(span) = is_other {
+            if other.is_some() {
+                combine(errors, syn::Error::new(span, "duplicate `other` variant"));
+                continue;
+            }
+            let members: Vec<_> = variant
+                .fields
+                .iter()
+                .enumerate()
+                .map(|(i, field)| match &field.ident {
+                    Some(ident) => Member::Named(ident.clone()),
+                    None => Member::Unnamed(i.into()),
+                })
+                .collect();
+            match <[Member; 2]>::try_from(members) {
+                Ok([name, data]) => {
+                    other = Some(Other {
+                        ident: variant.ident.clone(),
+                        name,
+                        data,
+                    })
+                }
+                Err(_) => combine(
+                    errors,
+                    syn::Error::new_spanned(
+                        &variant.ident,
+                        "the `other` variant must have two fields: the variant name and its bytes",
+                    ),
+                ),
+            }
+            continue;
+        }
+
+        let mut fields = vec![];
+        for (i, field) in variant.fields.iter().enumerate() {
+            match DescaleField::new(i, field) {
+                Ok(DescaleField {
+                    required: Some(span),
+                    ..
+                }) => combine(
+                    errors,
+                    syn::Error::new(span, "`required` isn't supported in enum variants"),
+                ),
+                Ok(field) => fields.push(field),
+                Err(err) => combine(errors, err),
+            }
+        }
+        arms.push(Arm {
+            ident: variant.ident.clone(),
+            variant: name.unwrap_or_else(|| variant.ident.to_string()),
+            fields,
+        });
+    }
+    match other {
+        Some(other) => Ok(Body::Enum { arms, other }),
+        None => Err(syn::Error::new_spanned(
+            name,
+            "a Descale enum needs a `#[descale(other)]` variant for unknown variants",
+        )),
+    }
+}
+
+/// How many paths `field` adds to `Descale::paths`.
+fn width(field: &DescaleField, krate: &syn::Path, lt: &Lifetime) -> TokenStream2 {
+    let ty = &field.ty;
+    match field.convert {
+        Some(_) => quote!(1),
+        None => quote_spanned! {ty.span()=> <#ty as #krate::descale::DescaleField<#lt>>::PATHS },
+    }
+}
+
+/// Calls that hand what's at `path`, below the end of `field`'s path, to
+/// `field`, which lives at `place` and whose paths start at index `at`: the
+/// `visit` one and the `variant` one.
+fn field_calls(
+    field: &DescaleField,
+    place: TokenStream2,
+    at: &TokenStream2,
+    krate: &syn::Path,
+    lt: &Lifetime,
+) -> (TokenStream2, TokenStream2) {
+    if let Some((convert, with)) = &field.convert {
+        // A converted field takes a single value, so has nothing below it.
+        let path = field.path.as_ref().unwrap().join(".");
+        // Only the function gets the converter's span: `path` and `data` are
+        // ours, and a converter from `descale!` has its hygiene.
+        let convert = quote_spanned! {with.span()=> #krate::descale::convert::#convert };
+        let visit = quote! {
+            if path.is_empty() {
+                seen.mark(#at);
+            }
+            #convert(#place, #path, #with, path, data)?;
+        };
+        return (visit, quote!());
+    }
+    let ty = &field.ty;
+    let field = quote_spanned! {ty.span()=> <#ty as #krate::descale::DescaleField<#lt>> };
+    let visit = quote! {
+        match path {
+            [] => {
+                seen.mark(#at);
+                #field::decode_into(#place, data);
+            }
+            rest => #field::visit_below(#place, rest, data, ty, types, seen.at(#at))?,
+        }
+    };
+    let variant = quote! {
+        if !path.is_empty() {
+            #field::variant_below(#place, path, data, seen.at(#at));
+        }
+    };
+    (visit, variant)
+}
+
+/// The fields' paths as a trie, expanded into nested matches on the
+/// walker's path so that a visit costs a match per segment however many
+/// fields there are.
+#[derive(Default)]
+struct Trie {
+    children: Vec<(String, Trie)>,
+    /// The child for a `*` segment.
+    any: Option<Box<Trie>>,
+    /// Indexes of the fields whose paths end here.
+    ends: Vec<usize>,
+}
+
+impl Trie {
+    fn insert(&mut self, segments: &[String], field: usize) {
+        match segments {
+            [] => self.ends.push(field),
+            [first, rest @ ..] if first == "*" => self
+                .any
+                .get_or_insert_with(Default::default)
+                .insert(rest, field),
+            [first, rest @ ..] => {
+                let child = match self.children.iter().position(|(name, _)| name == first) {
+                    Some(found) => found,
+                    None => {
+                        self.children.push((first.clone(), Trie::default()));
+                        self.children.len() - 1
+                    }
+                };
+                self.children[child].1.insert(rest, field);
+            }
+        }
+    }
+
+    /// Code running `calls[i]` for each field `i` whose path starts `path`,
+    /// with `path` rebound to what's left of it.
+    fn expand(&self, calls: &[TokenStream2]) -> TokenStream2 {
+        let ends = self.ends.iter().map(|&i| &calls[i]);
+        let names = self.children.iter().map(|(name, _)| name);
+        let children = self.children.iter().map(|(_, child)| child.expand(calls));
+        let named = (!self.children.is_empty()).then(|| {
+            quote! {
+                match segment.as_str() {
+                    #(#names => { #children })*
+                    _ => {}
+                }
+            }
+        });
+        let any = self.any.as_ref().map(|child| child.expand(calls));
+        let below = (named.is_some() || any.is_some()).then(|| {
+            quote! {
+                if let [(segment, _), path @ ..] = path {
+                    #named
+                    #any
+                }
+            }
+        });
+        quote! {
+            #({ #ends })*
+            #below
+        }
+    }
+}
+
+/// The `visit` and `variant` code for `fields`, each with the place it
+/// lives, whose paths start at index `at`. Also where the paths after them
+/// start.
+fn dispatch(
+    fields: &[(&DescaleField, TokenStream2)],
+    mut at: TokenStream2,
+    krate: &syn::Path,
+    lt: &Lifetime,
+) -> (TokenStream2, TokenStream2, TokenStream2) {
+    let mut trie = Trie::default();
+    let mut visits = vec![];
+    let mut variants = vec![];
+    for (i, (field, place)) in fields.iter().enumerate() {
+        trie.insert(field.path.as_ref().unwrap(), i);
+        let (visit, variant) = field_calls(field, place.clone(), &at, krate, lt);
+        visits.push(visit);
+        variants.push(variant);
+        let width = width(field, krate, lt);
+        at = quote!(#at + #width);
+    }
+    (trie.expand(&visits), trie.expand(&variants), at)
+}
+
+fn default_of(field: &DescaleField) -> TokenStream2 {
+    let member = &field.member;
+    let ty = &field.ty;
+    quote_spanned! {ty.span()=> #member: <#ty as ::core::default::Default>::default() }
+}
+
+fn descale_path(field: &DescaleField, prefix: &str, krate: &syn::Path) -> TokenStream2 {
+    let ty = &field.ty;
+    let path = format!("{}{}", prefix, field.path.as_ref().unwrap().join("."));
+    let required = field.required.map(|_| quote!(.required()));
+    match &field.convert {
+        Some((_, with)) => quote_spanned! {with.span()=>
+            #krate::descale::DescalePath::converted(#path, #with) #required
+        },
+        None => quote_spanned! {ty.span()=>
+            #krate::descale::DescalePath::new::<#ty>(#path) #required
+        },
+    }
+}
+
+/// The bodies of `empty`, `paths`, `visit` and `variant`, and how many
+/// paths there are.
+struct Generated {
+    empty: TokenStream2,
+    paths: Vec<TokenStream2>,
+    count: TokenStream2,
+    visit: TokenStream2,
+    variant: TokenStream2,
+}
+
+fn generate_struct(fields: &[DescaleField], krate: &syn::Path, lt: &Lifetime) -> Generated {
+    let defaults = fields.iter().map(default_of);
+    let placed: Vec<_> = fields
+        .iter()
+        .filter(|f| f.path.is_some())
+        .map(|field| {
+            let member = &field.member;
+            (field, quote!(&mut self.#member))
+        })
+        .collect();
+    let (visit, variant, count) = dispatch(&placed, quote!(0), krate, lt);
+    Generated {
+        empty: quote!(Self { #(#defaults,)* }),
+        paths: fields
+            .iter()
+            .filter(|f| f.path.is_some())
+            .map(|field| descale_path(field, "", krate))
+            .collect(),
+        count,
+        visit,
+        variant,
+    }
+}
+
+fn generate_enum(arms: &[Arm], other: &Other, krate: &syn::Path, lt: &Lifetime) -> Generated {
+    let mut paths = vec![];
+    let mut visits = vec![];
+    let mut variants = vec![];
+    // The index of the arm's variant path; its fields' paths follow it.
+    let mut at = quote!(0);
+    for arm in arms {
+        let ident = &arm.ident;
+        let name = &arm.variant;
+        paths.push(quote!(#krate::descale::DescalePath::variant(#name)));
+        let prefix = format!("{}.", name);
+        paths.extend(
+            arm.fields
+                .iter()
+                .filter(|f| f.path.is_some())
+                .map(|field| descale_path(field, &prefix, krate)),
+        );
+
+        let defaults: Vec<_> = arm.fields.iter().map(default_of).collect();
+        let mut members = vec![];
+        let mut placed = vec![];
+        for (i, field) in arm.fields.iter().enumerate() {
+            if field.path.is_some() {
+                members.push(&field.member);
+                placed.push((field, format_ident!("__field{}", i).into_token_stream()));
+            }
+        }
+        let bindings: Vec<_> = placed.iter().map(|(_, binding)| binding).collect();
+        let (visit, variant, next) = dispatch(&placed, quote!(#at + 1), krate, lt);
+        at = next;
+        // Switch to the arm the data is in, then hand its fields the rest
+        // of the path.
+        let select = |calls| {
+            quote! {
+                #name => {
+                    if !::core::matches!(self, Self::#ident { .. }) {
+                        *self = Self::#ident { #(#defaults,)* };
+                    }
+                    if let Self::#ident { #(#members: #bindings,)* .. } = self {
+                        let path = rest;
+                        #calls
+                    }
+                }
+            }
+        };
+        visits.push(select(visit));
+        variants.push(select(variant));
+    }
+
+    let Other {
+        ident,
+        name,
+        data: bytes,
+    } = other;
+    // Any variant satisfies a required enum, so mark the first variant's
+    // path for all of them. With no arms there's no path to mark.
+    let mark = (!arms.is_empty()).then(|| {
+        quote! {
+            if rest.is_empty() {
+                seen.mark(0);
+            }
+        }
+    });
+    Generated {
+        empty: quote! {
+            Self::#ident {
+                #name: ::core::default::Default::default(),
+                #bytes: ::core::default::Default::default(),
+            }
+        },
+        paths,
+        count: at,
+        visit: quote! {
+            if let [(variant, _), rest @ ..] = path {
+                match variant.as_str() {
+                    #(#visits)*
+                    _ => {}
+                }
+            }
+        },
+        variant: quote! {
+            if let [(variant, _), rest @ ..] = path {
+                #mark
+                match variant.as_str() {
+                    #(#variants)*
+                    _ if rest.is_empty() => {
+                        *self = Self::#ident { #name: variant.as_name().unwrap_or_default(), #bytes: data };
+                    }
+                    _ => {}
+                }
+            }
+        },
+    }
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let mut errors = None;
+    let container = Container::from_attrs(&input.attrs).unwrap_or_else(|err| {
+        combine(&mut errors, err);
+        Container {
+            krate: parse_quote!(::scale_borrow),
+        }
+    });
+    let body = match &input.data {
+        Data::Struct(data) => struct_body(&data.fields, &mut errors),
+        Data::Enum(data) => match enum_body(&input.ident, data, &mut errors) {
+            Ok(body) => body,
+            Err(err) => {
+                combine(&mut errors, err);
+                return Err(errors.unwrap());
+            }
+        },
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "Descale can't be derived for unions",
+            ))
+        }
+    };
+    if let Some(errors) = errors {
+        return Err(errors);
+    }
+
+    let krate = &container.krate;
+    let name = &input.ident;
+
+    // The type's own lifetime is the one the input bytes and registry are
+    // borrowed for; a type that borrows nothing gets a fresh one.
+    let struct_lt = input
+        .generics
+        .lifetimes()
+        .next()
+        .map(|l| l.lifetime.clone());
+    let lt = struct_lt
+        .clone()
+        .unwrap_or_else(|| Lifetime::new("'scale", Span::call_site()));
+    let mut visit_generics = input.generics.clone();
+    if struct_lt.is_none() {
+        visit_generics.params.insert(0, parse_quote!(#lt));
+    }
+
+    let fields: Vec<&DescaleField> = match &body {
+        Body::Struct(fields) => fields.iter().collect(),
+        Body::Enum { arms, .. } => arms.iter().flat_map(|arm| &arm.fields).collect(),
+    };
+    {
+        // Only generic field types need bounds; for the others an
+        // unsupported type is reported on the field by the generated calls.
+        let params: Vec<_> = input.generics.type_params().map(|p| &p.ident).collect();
+        let where_clause = visit_generics.make_where_clause();
+        for field in fields
+            .iter()
+            .filter(|f| mentions(f.ty.to_token_stream(), &params))
+        {
+            let ty = &field.ty;
+            where_clause
+                .predicates
+                .push(parse_quote_spanned! {ty.span()=> #ty: ::core::default::Default });
+            if field.path.is_some() && field.convert.is_none() {
+                where_clause
+                    .predicates
+                    .push(parse_quote_spanned! {ty.span()=>
+                        #ty: #krate::descale::DescaleField<#lt>
+                    });
+            }
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let (visit_impl_generics, _, visit_where_clause) = visit_generics.split_for_impl();
+
+    let Generated {
+        empty,
+        paths,
+        count,
+        visit,
+        variant,
+    } = match &body {
+        Body::Struct(fields) => generate_struct(fields, krate, &lt),
+        Body::Enum { arms, other } => generate_enum(arms, other, krate, &lt),
+    };
+
+    let parse_lt = match struct_lt {
+        Some(_) => quote!(),
+        None => quote!(<#lt>),
+    };
+
+    // A static in a generic impl is shared by every instantiation, so only
+    // a type without type parameters can cache its requirements.
+    let requirements = input
+        .generics
+        .params
+        .iter()
+        .all(|p| matches!(p, syn::GenericParam::Lifetime(_)))
+        .then(|| {
+            quote! {
+                fn requirements() -> ::std::borrow::Cow<'static, #krate::descale::Requirements> {
+                    static REQUIREMENTS: ::std::sync::OnceLock<#krate::descale::Requirements> =
+                        ::std::sync::OnceLock::new();
+                    ::std::borrow::Cow::Borrowed(REQUIREMENTS.get_or_init(|| {
+                        #krate::descale::Requirements::new(
+                            <Self as #krate::descale::Descale<#lt>>::paths(),
+                        )
+                    }))
+                }
+            }
+        });
+
+    Ok(quote! {
+        impl #impl_generics #name #ty_generics #where_clause {
+            /// Decode the fields with a `#[descale(path = ..)]` from `data`,
+            /// which holds a value of type `top_type` in `types`.
+            pub fn parse #parse_lt (
+                data: &#lt [u8],
+                top_type: impl #krate::descale::IntoTypeId,
+                types: &#lt #krate::scale_info::PortableRegistry,
+            ) -> Self
+            where
+                Self: #krate::descale::Descale<#lt>,
+            {
+                <Self as #krate::descale::Descale<#lt>>::parse(
+                    data,
+                    #krate::descale::IntoTypeId::into_type_id(top_type),
+                    types,
+                )
+            }
+        }
+
+        impl #visit_impl_generics #krate::descale::Descale<#lt> for #name #ty_generics #visit_where_clause {
+            fn empty() -> Self {
+                #empty
+            }
+
+            fn paths() -> ::std::vec::Vec<#krate::descale::DescalePath> {
+                #krate::descale::DescalePath::flatten(::std::vec![#(#paths),*])
+            }
+
+            #[allow(unused_variables, unused_mut)]
+            fn try_visit(
+                &mut self,
+                path: &[(#krate::PathSegment<#lt>, u32)],
+                data: &#lt [u8],
+                ty: &#lt #krate::scale_info::Type<#krate::scale_info::form::PortableForm>,
+                types: &#lt #krate::scale_info::PortableRegistry,
+                mut seen: #krate::descale::Seen<'_>,
+            ) -> ::core::result::Result<(), #krate::descale::ParseError> {
+                #visit
+                ::core::result::Result::Ok(())
+            }
+
+            #[allow(unused_variables, unused_mut)]
+            fn try_variant(
+                &mut self,
+                path: &[(#krate::PathSegment<#lt>, u32)],
+                data: &#lt [u8],
+                mut seen: #krate::descale::Seen<'_>,
+            ) {
+                #variant
+            }
+
+            #requirements
+        }
+
+        impl #visit_impl_generics #krate::descale::DescaleField<#lt> for #name #ty_generics #visit_where_clause {
+            const MANY: bool = false;
+
+            const PATHS: usize = #count;
+
+            fn accepts(
+                _ty: &#krate::scale_info::Type<#krate::scale_info::form::PortableForm>,
+                _types: &#krate::scale_info::PortableRegistry,
+            ) -> bool {
+                false
+            }
+
+            fn decode_into(&mut self, _data: &#lt [u8]) {}
+
+            fn nested() -> ::core::option::Option<fn() -> ::std::vec::Vec<#krate::descale::DescalePath>> {
+                ::core::option::Option::Some(<Self as #krate::descale::Descale<#lt>>::paths)
+            }
+
+            fn visit_below(
+                &mut self,
+                rest: &[(#krate::PathSegment<#lt>, u32)],
+                data: &#lt [u8],
+                ty: &#lt #krate::scale_info::Type<#krate::scale_info::form::PortableForm>,
+                types: &#lt #krate::scale_info::PortableRegistry,
+                seen: #krate::descale::Seen<'_>,
+            ) -> ::core::result::Result<(), #krate::descale::ParseError> {
+                #krate::descale::Descale::try_visit(self, rest, data, ty, types, seen)
+            }
+
+            fn variant_below(
+                &mut self,
+                rest: &[(#krate::PathSegment<#lt>, u32)],
+                data: &#lt [u8],
+                seen: #krate::descale::Seen<'_>,
+            ) {
+                #krate::descale::Descale::try_variant(self, rest, data, seen);
+            }
+        }
+
+        impl #visit_impl_generics #krate::VisitScale<#lt> for #name #ty_generics #visit_where_clause {
+            fn visit(
+                &mut self,
+                path: &[(#krate::PathSegment<#lt>, u32)],
+                data: &#lt [u8],
+                ty: &#lt #krate::scale_info::Type<#krate::scale_info::form::PortableForm>,
+                types: &#lt #krate::scale_info::PortableRegistry,
+            ) {
+                // Walked on its own there's no parse to fail, so a failed
+                // converter just leaves its field alone.
+                let seen = #krate::descale::Seen::none();
+                let _ = #krate::descale::Descale::try_visit(self, path, data, ty, types, seen);
+            }
+
+            fn variant(&mut self, path: &[(#krate::PathSegment<#lt>, u32)], data: &#lt [u8]) {
+                let seen = #krate::descale::Seen::none();
+                #krate::descale::Descale::try_variant(self, path, data, seen);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(input: DeriveInput) -> String {
+        match expand(input) {
+            Ok(tokens) => panic!("expected an error, got {}", tokens),
+            Err(err) => err
+                .into_iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+
+    #[test]
+    fn expands() {
+        let tokens = expand(parse_quote! {
+            /// Docs are kept.
+            #[derive(Debug)]
+            pub(crate) struct X<'a, T> {
+                #[descale(path = "outer.0.val")]
+                pub val: T,
+                name: &'a str,
+            }
+        })
+        .unwrap()
+        .to_string();
+        assert!(tokens.contains("impl < 'a , T > X < 'a , T >"));
+        assert!(tokens.contains("match segment . as_str () { \"outer\" =>"));
+        assert!(tokens.contains("match segment . as_str () { \"val\" =>"));
+        assert!(tokens.contains(":: scale_borrow :: descale :: Descale < 'a > for X"));
+        assert!(tokens.contains("DescalePath :: new :: < T > (\"outer.0.val\")"));
+
+        let tokens = expand(parse_quote! {
+            #[descale(crate = "sb")]
+            struct Owned(#[descale(path = "0")] u32);
+        })
+        .unwrap()
+        .to_string();
+        assert!(tokens.contains("sb :: VisitScale < 'scale >"));
+        assert!(tokens.contains("decode_into (& mut self . 0 , data)"));
+
+        let tokens = expand(parse_quote! {
+            struct Required(#[descale(path = "0", required)] u32);
+        })
+        .unwrap()
+        .to_string();
+        assert!(tokens.contains("DescalePath :: new :: < u32 > (\"0\") . required ()"));
+
+        let tokens = expand(parse_quote! {
+            struct Converted<T> {
+                #[descale(path = "who", try_with = ss58)]
+                who: T,
+                #[descale(path = "amount", with = units::milli)]
+                amount: u128,
+            }
+        })
+        .unwrap()
+        .to_string();
+        assert!(tokens
+            .contains("convert :: try_with (& mut self . who , \"who\" , ss58 , path , data)"));
+        assert!(tokens.contains("DescalePath :: converted (\"who\" , ss58)"));
+        assert!(tokens.contains("DescalePath :: converted (\"amount\" , units :: milli)"));
+        // Converted generic fields only need to be `Default`.
+        assert!(tokens.contains("where T : :: core :: default :: Default {"));
+
+        // As `descale!` passes them on.
+        let tokens = expand(parse_quote! {
+            #[descale(crate = sb)]
+            struct Closure {
+                #[descale(path = "x", with = (|x: u8| x * 2))]
+                doubled: u8,
+            }
+        })
+        .unwrap()
+        .to_string();
+        assert!(tokens.contains("sb :: VisitScale < 'scale >"));
+        assert!(tokens.contains("DescalePath :: converted (\"x\" , (| x : u8 | x * 2))"));
+
+        // Paths sharing a prefix share the match on it, and a `*` is tried
+        // after the named segments.
+        let tokens = expand(parse_quote! {
+            struct Shared {
+                #[descale(path = "a.b")]
+                b: u8,
+                #[descale(path = "a.*.c")]
+                c: Vec<u8>,
+                #[descale(path = "a.d")]
+                d: u8,
+            }
+        })
+        .unwrap()
+        .to_string();
+        assert_eq!(tokens.matches("\"a\" =>").count(), 2);
+        assert!(tokens.contains(
+            "match segment . as_str () { \"b\" => { { match path { [] => { seen . mark (0) ; < u8 as :: scale_borrow :: descale :: DescaleField < 'scale >> :: decode_into (& mut self . b , data)"
+        ));
+        // `d`'s index is after `b`'s and `c`'s paths.
+        assert!(tokens.contains(
+            "\"d\" => { { match path { [] => { seen . mark (0 + < u8 as :: scale_borrow :: descale :: DescaleField < 'scale >> :: PATHS + < Vec < u8 > as :: scale_borrow :: descale :: DescaleField < 'scale >> :: PATHS) ; < u8 as :: scale_borrow :: descale :: DescaleField < 'scale >> :: decode_into (& mut self . d , data)"
+        ));
+        assert!(tokens.contains(
+            "_ => { } } if let [(segment , _) , path @ ..] = path { match segment . as_str () { \"c\" =>"
+        ));
+
+        let tokens = expand(parse_quote! {
+            enum Call<'a> {
+                Transfer { #[descale(path = "dest")] dest: &'a [u8] },
+                #[descale(variant = "remark")]
+                Remark(#[descale(path = "0")] &'a [u8], u8),
+                #[descale(other)]
+                Other(&'a str, &'a [u8]),
+            }
+        })
+        .unwrap()
+        .to_string();
+        assert!(tokens.contains("DescalePath :: variant (\"Transfer\")"));
+        assert!(tokens.contains("DescalePath :: new :: < & 'a [u8] > (\"Transfer.dest\")"));
+        assert!(tokens.contains("DescalePath :: variant (\"remark\")"));
+        assert!(tokens.contains("DescalePath :: new :: < & 'a [u8] > (\"remark.0\")"));
+        assert!(tokens.contains("Self :: Remark { 0 : __field0 , .. } = self"));
+        assert!(tokens.contains(
+            "Self :: Other { 0 : variant . as_name () . unwrap_or_default () , 1 : data }"
+        ));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            error(parse_quote! { union U { a: u8 } }),
+            "Descale can't be derived for unions"
+        );
+        assert_eq!(
+            error(parse_quote! {
+                enum E {
+                    #[descale(variant = "a", variant = "b")]
+                    A,
+                    #[descale(name = "b")]
+                    B { #[descale(path = "x", required)] x: u8 },
+                    #[descale(other)]
+                    C(u8),
+                    #[descale(other, other)]
+                    D(&'static str, &'static [u8]),
+                    #[descale(other)]
+                    F(&'static str, &'static [u8]),
+                }
+            }),
+            "duplicate `variant`\n\
+             unknown descale variant option, expected `variant` or `other`\n\
+             `required` isn't supported in enum variants\n\
+             the `other` variant must have two fields: the variant name and its bytes\n\
+             duplicate `other`\n\
+             duplicate `other` variant"
+        );
+        assert_eq!(
+            error(parse_quote! { enum E { A } }),
+            "a Descale enum needs a `#[descale(other)]` variant for unknown variants"
+        );
+        assert_eq!(
+            error(parse_quote! {
+                struct S {
+                    #[descale(path = "a..b")]
+                    a: u8,
+                    #[descale(path = "")]
+                    b: u8,
+                    #[descale(path = "c", path = "d")]
+                    c: u8,
+                    #[descale(path = 3)]
+                    d: u8,
+                    #[descale(paths = "e")]
+                    e: u8,
+                    #[descale(required)]
+                    f: u8,
+                    #[descale(path = "g", required, required)]
+                    g: u8,
+                    #[descale(with = f)]
+                    h: u8,
+                    #[descale(path = "i", with = f, try_with = g)]
+                    i: u8,
+                }
+            }),
+            "empty segment in path \"a..b\"; segments are separated by a single `.`\n\
+             path must not be empty\n\
+             duplicate `path`\n\
+             expected string literal\n\
+             unknown descale field option, expected `path`, `required`, `with` or `try_with`\n\
+             `required` needs a `path`\n\
+             duplicate `required`\n\
+             `with` needs a `path`\n\
+             only one of `with` and `try_with` can be given"
+        );
+        assert_eq!(
+            error(parse_quote! {
+                #[descale(krate = "x")]
+                struct S {}
+            }),
+            "unknown descale option, expected `crate`"
+        );
+    }
+}
@@ -0,0 +1,351 @@
+//! Arena allocated value trees (the `arena` feature).
+//!
+//! [`ValueBuilder`](crate::ValueBuilder) allocates a `Box<Vec<_>>` per object
+//! and a box per `u128`. [`ArenaBuilder`] builds the same shape but grows
+//! each object's child list in a [`Bump`] arena and freezes it into a slice
+//! when the walker leaves the object. The resulting [`ArenaValue`] is `Copy` and lives as long
+//! as the arena; resetting the arena frees a whole tree at once.
+//!
+//! ```
+//! # use bumpalo::Bump;
+//! # use parity_scale_codec::Encode;
+//! # use scale_borrow::arena::ArenaBuilder;
+//! # let mut registry = scale_info::Registry::new();
+//! # let block_ty = registry.register_type(&scale_info::MetaType::new::<Vec<u32>>()).id();
+//! # let types: scale_info::PortableRegistry = registry.into();
+//! # let blocks = vec![vec![1u32, 2].encode(), vec![3u32].encode()];
+//! let mut bump = Bump::new();
+//! for block in blocks {
+//!     let val = ArenaBuilder::parse(&block, block_ty, &types, &bump);
+//!     // ... use val ...
+//! #   assert!(val.get("0").is_some());
+//!     bump.reset();
+//! }
+//! ```
+use crate::borrow_decode::BorrowDecode;
+use crate::value::Wrapper;
+use crate::{PathSegment, Value};
+use bumpalo::collections::Vec as BumpVec;
+use bumpalo::Bump;
+use parity_scale_codec::Compact;
+use scale_info::form::PortableForm;
+use scale_info::{PortableRegistry, Type, TypeDef, TypeDefPrimitive};
+
+/// Like [`Value`] but with child lists and big numbers in an arena.
+///
+/// It only has variants for what the decoder produces, and doesn't represent:
+///
+/// * bit sequences, which are kept as their raw `Scale` bytes even with the
+///   `bitvec` feature
+/// * owned strings and bytes, so there's no `into_owned`; use
+///   [`to_value`](Self::to_value) and [`Value::into_owned`] for that
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArenaValue<'a, 'scale> {
+    Object(&'a [(&'a str, ArenaValue<'a, 'scale>)]),
+    Bool(bool),
+    Str(&'scale str),
+    Scale(&'scale [u8]),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    U128(&'a u128),
+    None,
+    Some(&'a ArenaValue<'a, 'scale>),
+    Ok(&'a ArenaValue<'a, 'scale>),
+    Err(&'a ArenaValue<'a, 'scale>),
+}
+
+impl<'a, 'scale> ArenaValue<'a, 'scale> {
+    pub fn find(&self, find1: &str) -> Option<&'a Self> {
+        match self {
+            Self::Object(fields) => fields
+                .iter()
+                .find(|(name, _)| *name == find1)
+                .map(|(_, val)| val),
+            // So `Some`, `Ok` and `Err` work in paths like other variants.
+            Self::Some(val) if find1 == "Some" => Some(val),
+            Self::Ok(val) if find1 == "Ok" => Some(val),
+            Self::Err(val) if find1 == "Err" => Some(val),
+            _ => None,
+        }
+    }
+
+    /// Follow a dotted path of field names, as [`Value::get`].
+    pub fn get(&self, path: &str) -> Option<&'a Self> {
+        let mut parts = path.split('.');
+        let first = self.find(parts.next()?)?;
+        parts.try_fold(first, |cur, pa| cur.find(pa))
+    }
+
+    /// The registry type id of an object (its `_ty` field).
+    pub fn type_id(&self) -> Option<u32> {
+        match self.find("_ty") {
+            Some(Self::U32(id)) => Some(*id),
+            _ => None,
+        }
+    }
+
+    /// Copy out of the arena into a heap allocated [`Value`], borrowing the
+    /// field names from the arena.
+    pub fn to_value(&self) -> Value<'a>
+    where
+        'scale: 'a,
+    {
+        match *self {
+            Self::Object(fields) => Value::Object(Box::new(
+                fields
+                    .iter()
+                    .map(|(name, val)| ((*name).into(), val.to_value()))
+                    .collect(),
+            )),
+            Self::Bool(b) => Value::Bool(b),
+            Self::Str(s) => Value::Str(s.into()),
+            Self::Scale(bytes) => Value::Scale(bytes),
+            Self::U8(n) => Value::U8(n),
+            Self::U16(n) => Value::U16(n),
+            Self::U32(n) => Value::U32(n),
+            Self::U64(n) => Value::U64(n),
+            Self::U128(n) => Value::U128(Box::new(*n)),
+            Self::None => Value::None,
+            Self::Some(val) => Value::Some(Box::new(val.to_value())),
+            Self::Ok(val) => Value::Ok(Box::new(val.to_value())),
+            Self::Err(val) => Value::Err(Box::new(val.to_value())),
+        }
+    }
+}
+
+type Fields<'a, 'scale> = BumpVec<'a, (&'a str, ArenaValue<'a, 'scale>)>;
+
+pub struct ArenaBuilder<'a, 'scale> {
+    bump: &'a Bump,
+    root: Option<ArenaValue<'a, 'scale>>,
+    /// Objects entered but not yet left, outermost first, and whether each is
+    /// an `Option` or `Result` to be turned into its native form when left.
+    open: Vec<(&'a str, Fields<'a, 'scale>, Wrapper)>,
+}
+
+impl<'a, 'scale: 'a> ArenaBuilder<'a, 'scale> {
+    pub fn parse(
+        data: &'scale [u8],
+        top_type_id: u32,
+        types: &'scale PortableRegistry,
+        bump: &'a Bump,
+    ) -> ArenaValue<'a, 'scale> {
+        let mut slf = ArenaBuilder {
+            bump,
+            root: None,
+            open: vec![],
+        };
+        crate::skeleton_decode(data, top_type_id, &mut slf, types);
+        slf.root.unwrap()
+    }
+
+    /// The field name for the last segment of `path`, with positions
+    /// formatted into the arena.
+    fn field_name(&self, path: &[(PathSegment<'scale>, u32)]) -> &'a str {
+        match path.last() {
+            Some((segment, _)) => match segment.as_name() {
+                Some(name) => name,
+                None => self.bump.alloc_str(segment),
+            },
+            None => "",
+        }
+    }
+
+    /// Add a finished value to the innermost open object (or make it the root).
+    fn place(&mut self, name: &'a str, val: ArenaValue<'a, 'scale>) {
+        match self.open.last_mut() {
+            Some((_, fields, _)) => fields.push((name, val)),
+            None => self.root = Some(val),
+        }
+    }
+
+    fn leaf(
+        &self,
+        data: &'scale [u8],
+        ty: &Type<PortableForm>,
+        types: &PortableRegistry,
+    ) -> ArenaValue<'a, 'scale> {
+        match ty.type_def() {
+            TypeDef::Primitive(TypeDefPrimitive::Str) => {
+                ArenaValue::Str(<&'scale str>::borrow_decode(data))
+            }
+            TypeDef::Primitive(TypeDefPrimitive::Bool) => {
+                ArenaValue::Bool(bool::borrow_decode(data))
+            }
+            TypeDef::Primitive(TypeDefPrimitive::U8) => ArenaValue::U8(u8::borrow_decode(data)),
+            TypeDef::Primitive(TypeDefPrimitive::U16) => ArenaValue::U16(u16::borrow_decode(data)),
+            TypeDef::Primitive(TypeDefPrimitive::U32) => ArenaValue::U32(u32::borrow_decode(data)),
+            TypeDef::Primitive(TypeDefPrimitive::U64) => ArenaValue::U64(u64::borrow_decode(data)),
+            TypeDef::Primitive(TypeDefPrimitive::U128) => {
+                ArenaValue::U128(self.bump.alloc(u128::borrow_decode(data)))
+            }
+            // Only u8 sequences and bit sequences get here.
+            TypeDef::Sequence(_) | TypeDef::Array(_) | TypeDef::BitSequence(_) => {
+                ArenaValue::Scale(data)
+            }
+            TypeDef::Compact(inner) => {
+                let inner = types.resolve(inner.type_param().id()).unwrap();
+                match inner.type_def() {
+                    TypeDef::Primitive(TypeDefPrimitive::U32) => {
+                        ArenaValue::U32(Compact::<u32>::borrow_decode(data).into())
+                    }
+                    TypeDef::Primitive(TypeDefPrimitive::U64) => {
+                        ArenaValue::U64(Compact::<u64>::borrow_decode(data).into())
+                    }
+                    TypeDef::Primitive(TypeDefPrimitive::U128) => ArenaValue::U128(
+                        self.bump.alloc(Compact::<u128>::borrow_decode(data).into()),
+                    ),
+                    _ => panic!("unsupported {:?}", inner),
+                }
+            }
+            _ => panic!("skipping {:?}", ty),
+        }
+    }
+
+    /// The native form of a finished `{_ty, Variant: {_ty, 0?}}` object, as
+    /// [`Wrapper::convert`], or the object as is if it's not that shape.
+    fn convert(&self, wrapper: Wrapper, fields: Fields<'a, 'scale>) -> ArenaValue<'a, 'scale> {
+        let fields = fields.into_bump_slice();
+        if let [_, (name, ArenaValue::Object(payload))] = fields {
+            match (wrapper, *name, &payload[1..]) {
+                (Wrapper::Option, "None", []) => return ArenaValue::None,
+                (Wrapper::Option, "Some", [("0", inner)]) => return ArenaValue::Some(inner),
+                (Wrapper::Result, "Ok", [("0", inner)]) => return ArenaValue::Ok(inner),
+                (Wrapper::Result, "Err", [("0", inner)]) => return ArenaValue::Err(inner),
+                _ => {}
+            }
+        }
+        ArenaValue::Object(fields)
+    }
+}
+
+fn new_object<'a, 'scale>(bump: &'a Bump, type_id: u32) -> Fields<'a, 'scale> {
+    let mut fields = BumpVec::new_in(bump);
+    fields.push(("_ty", ArenaValue::U32(type_id)));
+    fields
+}
+
+impl<'a, 'scale: 'a> crate::VisitScale<'scale> for ArenaBuilder<'a, 'scale> {
+    fn visit(
+        &mut self,
+        current_path: &[(PathSegment<'scale>, u32)],
+        data: &'scale [u8],
+        ty: &'scale Type<PortableForm>,
+        types: &'scale PortableRegistry,
+    ) {
+        let leaf = self.leaf(data, ty, types);
+        let name = self.field_name(current_path);
+        self.place(name, leaf);
+    }
+
+    fn enter(
+        &mut self,
+        path: &[(PathSegment<'scale>, u32)],
+        ty_id: u32,
+        ty: &'scale Type<PortableForm>,
+    ) {
+        let name = self.field_name(path);
+        // Only the enum's object gets converted, not its variant's.
+        let wrapper = match self.open.last() {
+            Some((_, fields, w)) if *w != Wrapper::No && fields.len() == 1 => Wrapper::No,
+            _ => Wrapper::of(ty),
+        };
+        self.open
+            .push((name, new_object(self.bump, ty_id), wrapper));
+    }
+
+    fn leave(&mut self, _path: &[(PathSegment<'scale>, u32)]) {
+        let (name, fields, wrapper) = self.open.pop().expect("leave without enter");
+        let val = self.convert(wrapper, fields);
+        self.place(name, val);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::make_type_id;
+    use crate::ValueBuilder;
+    use parity_scale_codec::Encode;
+
+    #[derive(Encode, scale_info::TypeInfo)]
+    enum Event {
+        Transfer {
+            from: [u8; 4],
+            to: [u8; 4],
+            #[codec(compact)]
+            amount: u128,
+        },
+        Remark(String),
+    }
+
+    #[derive(Encode, scale_info::TypeInfo)]
+    struct Record {
+        index: u32,
+        event: Event,
+        topics: Vec<u64>,
+        parent: Option<u32>,
+        outcome: Result<u8, String>,
+    }
+
+    #[test]
+    fn same_as_value_builder() {
+        let records = vec![
+            Record {
+                index: 0,
+                event: Event::Transfer {
+                    from: [1; 4],
+                    to: [2; 4],
+                    amount: u128::MAX,
+                },
+                topics: vec![1, 2],
+                parent: None,
+                outcome: Ok(3),
+            },
+            Record {
+                index: 1,
+                event: Event::Remark("hi".into()),
+                topics: vec![3],
+                parent: Some(0),
+                outcome: Err("no".into()),
+            },
+        ];
+        let encoded = records.encode();
+        let (id, types) = make_type_id::<Vec<Record>>();
+
+        let bump = Bump::new();
+        let arena = ArenaBuilder::parse(&encoded, id, &types, &bump);
+        assert_eq!(arena.to_value(), ValueBuilder::parse(&encoded, id, &types));
+        assert_eq!(arena.get("1.event.Remark.0"), Some(&ArenaValue::Str("hi")));
+        assert_eq!(
+            arena.get("0.event.Transfer.amount"),
+            Some(&ArenaValue::U128(&u128::MAX))
+        );
+        assert_eq!(arena.type_id(), Some(id));
+        assert_eq!(arena.get("0.parent"), Some(&ArenaValue::None));
+        assert_eq!(
+            arena.get("1.parent"),
+            Some(&ArenaValue::Some(&ArenaValue::U32(0)))
+        );
+        assert_eq!(arena.get("0.outcome.Ok"), Some(&ArenaValue::U8(3)));
+        assert_eq!(arena.get("1.outcome.Err"), Some(&ArenaValue::Str("no")));
+    }
+
+    #[test]
+    fn leaf_root() {
+        let encoded = 7u64.encode();
+        let (id, types) = make_type_id::<u64>();
+        let bump = Bump::new();
+        assert_eq!(
+            ArenaBuilder::parse(&encoded, id, &types, &bump),
+            ArenaValue::U64(7)
+        );
+    }
+
+    #[test]
+    fn size() {
+        assert_eq!(std::mem::size_of::<ArenaValue>(), 24);
+    }
+}
@@ -1,15 +1,29 @@
 use parity_scale_codec::{Compact, Decode};
+use scale_info::form::PortableForm;
+use scale_info::{PortableRegistry, Type, TypeDef, TypeDefPrimitive};
 
 pub trait BorrowDecode<'scale> {
     fn borrow_decode(data: &'scale [u8]) -> Self;
+
+    /// Whether the walker's data for a value of type `ty` can be decoded as
+    /// `Self`. Used by [`Descale::bind`](crate::descale::Descale::bind) to
+    /// check paths before decoding; the default trusts any type.
+    fn accepts(_ty: &Type<PortableForm>, _types: &PortableRegistry) -> bool {
+        true
+    }
 }
 
 impl<'scale> BorrowDecode<'scale> for &'scale str {
     fn borrow_decode(data: &'scale [u8]) -> Self {
         std::str::from_utf8(data).unwrap()
     }
+
+    fn accepts(ty: &Type<PortableForm>, _types: &PortableRegistry) -> bool {
+        matches!(ty.type_def(), TypeDef::Primitive(TypeDefPrimitive::Str))
+    }
 }
 
+// The raw bytes of whatever is there.
 impl<'scale> BorrowDecode<'scale> for &'scale [u8] {
     fn borrow_decode(data: &'scale [u8]) -> Self {
         data
@@ -17,17 +31,57 @@ impl<'scale> BorrowDecode<'scale> for &'scale [u8] {
 }
 
 macro_rules! impl_borrow_decode {
-    ($($t:ty)+) => {
+    ($($t:ty => $prim:ident)+) => {
         $(
             impl<'scale> BorrowDecode<'scale> for $t {
                 fn borrow_decode(mut data: &'scale [u8]) -> Self {
                     let d = &mut data;
                     <$t>::decode(d).unwrap()
                 }
+
+                fn accepts(ty: &Type<PortableForm>, _types: &PortableRegistry) -> bool {
+                    matches!(ty.type_def(), TypeDef::Primitive(TypeDefPrimitive::$prim))
+                }
+            }
+        )+
+    };
+}
+
+macro_rules! impl_borrow_decode_compact {
+    ($($t:ty => $prim:ident)+) => {
+        $(
+            impl<'scale> BorrowDecode<'scale> for Compact<$t> {
+                fn borrow_decode(mut data: &'scale [u8]) -> Self {
+                    let d = &mut data;
+                    <Compact<$t>>::decode(d).unwrap()
+                }
+
+                fn accepts(ty: &Type<PortableForm>, types: &PortableRegistry) -> bool {
+                    match ty.type_def() {
+                        TypeDef::Compact(inner) => matches!(
+                            types.resolve(inner.type_param().id()).map(|t| t.type_def()),
+                            Some(TypeDef::Primitive(TypeDefPrimitive::$prim))
+                        ),
+                        _ => false,
+                    }
+                }
             }
         )+
     };
 }
 
 // Just delegate to standard scale decode
-impl_borrow_decode!(bool u8 u16 u32 u64 u128 Compact<u32> Compact<u64> Compact<u128>);
+impl_borrow_decode!(bool => Bool u8 => U8 u16 => U16 u32 => U32 u64 => U64 u128 => U128);
+impl_borrow_decode_compact!(u32 => U32 u64 => U64 u128 => U128);
+
+// `None` until the path is visited, so a missing path can be told apart from
+// a zero or `false`.
+impl<'scale, T: BorrowDecode<'scale>> BorrowDecode<'scale> for Option<T> {
+    fn borrow_decode(data: &'scale [u8]) -> Self {
+        Some(T::borrow_decode(data))
+    }
+
+    fn accepts(ty: &Type<PortableForm>, types: &PortableRegistry) -> bool {
+        T::accepts(ty, types)
+    }
+}
@@ -0,0 +1,241 @@
+//! `Eq`, `Hash` and `Ord` for [`Value`], so decoded keys (account ids,
+//! storage map keys, ...) can go in `HashMap`s and `BTreeMap`s.
+//!
+//! All three agree with `PartialEq`: borrowed and owned forms of the same
+//! string or bytes are equal, hash the same and sort together, while numbers
+//! of different widths (`U8(1)` and `U32(1)`) are different values.
+use crate::Value;
+use core::cmp::Ordering;
+use core::hash::{Hash, Hasher};
+
+impl Value<'_> {
+    /// Position in the canonical order. Borrowed and owned forms share one.
+    fn rank(&self) -> u8 {
+        match self {
+            Value::Object(_) => 0,
+            Value::Bool(_) => 1,
+            Value::Char(_) => 2,
+            Value::Str(_) => 3,
+            Value::Scale(_) | Value::ScaleOwned(_) => 4,
+            Value::U8(_) => 5,
+            Value::U16(_) => 6,
+            Value::U32(_) => 7,
+            Value::U64(_) => 8,
+            Value::U128(_) => 9,
+            Value::I8(_) => 10,
+            Value::I16(_) => 11,
+            Value::I32(_) => 12,
+            Value::I64(_) => 13,
+            Value::I128(_) => 14,
+            Value::U256(_) | Value::U256Owned(_) => 15,
+            Value::I256(_) | Value::I256Owned(_) => 16,
+            Value::Bits(_) => 17,
+            Value::None => 18,
+            Value::Some(_) => 19,
+            Value::Ok(_) => 20,
+            Value::Err(_) => 21,
+        }
+    }
+}
+
+/// Compare little endian 256 bit unsigned numbers.
+fn cmp_u256(a: &[u8; 32], b: &[u8; 32]) -> Ordering {
+    a.iter().rev().cmp(b.iter().rev())
+}
+
+/// Compare little endian 256 bit two's complement numbers.
+fn cmp_i256(a: &[u8; 32], b: &[u8; 32]) -> Ordering {
+    (a[31] as i8)
+        .cmp(&(b[31] as i8))
+        .then_with(|| a[..31].iter().rev().cmp(b[..31].iter().rev()))
+}
+
+impl Eq for Value<'_> {}
+
+impl Hash for Value<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.rank().hash(state);
+        match self {
+            Value::Object(fields) => {
+                fields.len().hash(state);
+                for (name, val) in fields.iter() {
+                    name.as_ref().hash(state);
+                    val.hash(state);
+                }
+            }
+            Value::Str(s) => s.hash(state),
+            Value::Scale(_) | Value::ScaleOwned(_) => self.as_bytes().hash(state),
+            Value::Bool(b) => b.hash(state),
+            Value::Char(c) => c.hash(state),
+            Value::U8(n) => n.hash(state),
+            Value::U16(n) => n.hash(state),
+            Value::U32(n) => n.hash(state),
+            Value::U64(n) => n.hash(state),
+            Value::U128(n) => n.hash(state),
+            Value::I8(n) => n.hash(state),
+            Value::I16(n) => n.hash(state),
+            Value::I32(n) => n.hash(state),
+            Value::I64(n) => n.hash(state),
+            Value::I128(n) => n.hash(state),
+            Value::U256(_) | Value::U256Owned(_) => self.as_u256().hash(state),
+            Value::I256(_) | Value::I256Owned(_) => self.as_i256().hash(state),
+            Value::Bits(bits) => {
+                bits.len().hash(state);
+                bits.iter().for_each(|bit| bit.hash(state));
+            }
+            Value::None => {}
+            Value::Some(val) | Value::Ok(val) | Value::Err(val) => val.hash(state),
+        }
+    }
+}
+
+/// The canonical order: values of different variants sort in declaration order
+/// (borrowed and owned forms together):
+///
+/// `Object` < `Bool` < `Char` < `Str` < `Scale`/`ScaleOwned` <
+/// `U8` < `U16` < `U32` < `U64` < `U128` < `I8` < `I16` < `I32` < `I64` <
+/// `I128` < `U256`/`U256Owned` < `I256`/`I256Owned` < `Bits` < `None` < `Some` < `Ok` < `Err`
+///
+/// Within a variant, numbers (including the 256 bit ones) sort numerically,
+/// strings and bytes lexicographically, bit sequences bit by bit and objects
+/// field by field on (name, value), `_ty` included.
+impl Ord for Value<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        use Value::*;
+        match (self, other) {
+            (Object(a), Object(b)) => a
+                .iter()
+                .map(|(name, val)| (name.as_ref(), val))
+                .cmp(b.iter().map(|(name, val)| (name.as_ref(), val))),
+            (Str(a), Str(b)) => a.cmp(b),
+            (Scale(_) | ScaleOwned(_), Scale(_) | ScaleOwned(_)) => {
+                self.as_bytes().cmp(&other.as_bytes())
+            }
+            (Bool(a), Bool(b)) => a.cmp(b),
+            (Char(a), Char(b)) => a.cmp(b),
+            (U8(a), U8(b)) => a.cmp(b),
+            (U16(a), U16(b)) => a.cmp(b),
+            (U32(a), U32(b)) => a.cmp(b),
+            (U64(a), U64(b)) => a.cmp(b),
+            (U128(a), U128(b)) => a.cmp(b),
+            (I8(a), I8(b)) => a.cmp(b),
+            (I16(a), I16(b)) => a.cmp(b),
+            (I32(a), I32(b)) => a.cmp(b),
+            (I64(a), I64(b)) => a.cmp(b),
+            (I128(a), I128(b)) => a.cmp(b),
+            (U256(_) | U256Owned(_), U256(_) | U256Owned(_)) => {
+                cmp_u256(self.as_u256().unwrap(), other.as_u256().unwrap())
+            }
+            (I256(_) | I256Owned(_), I256(_) | I256Owned(_)) => {
+                cmp_i256(self.as_i256().unwrap(), other.as_i256().unwrap())
+            }
+            (Bits(a), Bits(b)) => a.iter().cmp(b.iter()),
+            (Some(a), Some(b)) | (Ok(a), Ok(b)) | (Err(a), Err(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl PartialOrd for Value<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{OwnedValue, Value};
+    use std::borrow::Cow;
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::{BTreeSet, HashSet};
+    use std::hash::{Hash, Hasher};
+
+    fn hash(val: &Value) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        val.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn account(id: &[u8]) -> Value<'_> {
+        Value::Object(Box::new(vec![
+            ("_ty".into(), Value::U32(3)),
+            ("0".into(), Value::Scale(id)),
+        ]))
+    }
+
+    #[test]
+    fn borrowed_and_owned_agree() {
+        let bytes = [1u8, 2, 3];
+        let borrowed = account(&bytes);
+        let owned: OwnedValue = borrowed.clone().into_owned();
+        assert!(matches!(owned.find("0"), Some(Value::ScaleOwned(_))));
+        assert_eq!(borrowed, owned);
+        assert_eq!(hash(&borrowed), hash(&owned));
+        assert_eq!(borrowed.cmp(&owned), core::cmp::Ordering::Equal);
+
+        let keys: HashSet<Value> = [borrowed, owned, account(&[4])].into_iter().collect();
+        assert_eq!(keys.len(), 2);
+
+        let s = Value::Str(Cow::Owned("hi".into()));
+        assert_eq!(hash(&Value::Str("hi".into())), hash(&s));
+        let n = [7; 32];
+        assert_eq!(hash(&Value::I256(&n)), hash(&Value::I256Owned(Box::new(n))));
+        assert_eq!(
+            hash(&Value::U128(Box::new(5))),
+            hash(&Value::U128(Box::new(5)))
+        );
+    }
+
+    #[test]
+    fn canonical_order() {
+        let neg_one = [0xff; 32];
+        let mut big = [0; 32];
+        big[31] = 1;
+        let sorted: Vec<Value> = [
+            Value::Err(Box::new(Value::U8(0))),
+            Value::I256(&big),
+            Value::I256Owned(Box::new(neg_one)),
+            Value::U256(&neg_one),
+            Value::U256(&big),
+            Value::U8(2),
+            Value::Some(Box::new(Value::Bool(false))),
+            Value::U32(1),
+            Value::None,
+            Value::Str("b".into()),
+            Value::ScaleOwned(Box::new(vec![0])),
+            Value::Str(Cow::Owned("a".into())),
+            account(&[9]),
+            Value::Bool(true),
+        ]
+        .into_iter()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+        assert_eq!(
+            sorted,
+            vec![
+                account(&[9]),
+                Value::Bool(true),
+                Value::Str(Cow::Owned("a".into())),
+                Value::Str("b".into()),
+                Value::ScaleOwned(Box::new(vec![0])),
+                Value::U8(2),
+                Value::U32(1),
+                Value::U256(&big),
+                Value::U256(&neg_one),
+                Value::I256(&neg_one),
+                Value::I256(&big),
+                Value::None,
+                Value::Some(Box::new(Value::Bool(false))),
+                Value::Err(Box::new(Value::U8(0))),
+            ]
+        );
+
+        // Field by field, so a smaller first field wins.
+        assert!(account(&[1, 9]) < account(&[2]));
+
+        let bits = |b: &[bool]| Value::Bits(Box::new(b.iter().copied().collect()));
+        assert!(bits(&[false, true]) < bits(&[true]));
+        assert_ne!(hash(&bits(&[true])), hash(&bits(&[true, false])));
+    }
+}
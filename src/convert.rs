@@ -0,0 +1,433 @@
+//! Conversions between [`Value`] and [`scale_value::Value`].
+//!
+//! The `u32` context of a `scale_value::Value` is the registry type id.
+//!
+//! The `From` impls work without the registry. Only objects record their type
+//! id (in `_ty`), so every other value gets a context of [`NO_TYPE_ID`]. Byte
+//! strings become sequences of numbers as in `scale_value`, with a context of
+//! [`BYTES`], and options and results become `None`/`Some`/`Ok`/`Err` variants,
+//! so converting back gives the original `Value` except that `scale_value` only
+//! has 128 and 256 bit integers: narrower ones come back as `U128`/`I128`.
+//!
+//! [`to_scale_value`] and [`from_scale_value`] go through the SCALE encoding of
+//! a registry type instead, so every context is a type id and converting back
+//! gives exactly what [`ValueBuilder::parse`](crate::ValueBuilder::parse) would.
+use crate::encode::{encode_value, EncodeError, EncodeErrorKind};
+use crate::{OwnedValue, Value, ValueBuilder};
+use scale_info::PortableRegistry;
+use scale_value::{Composite, Primitive, ValueDef, Variant};
+use std::borrow::Cow;
+
+/// The context of a value with no type id, i.e. anything but an object.
+pub const NO_TYPE_ID: u32 = u32::MAX;
+
+/// The context of a byte string (`Scale`), so it converts back as one rather
+/// than as an object of numbers.
+pub const BYTES: u32 = u32::MAX - 1;
+
+/// Convert `val` as registry type `type_id`, with the type id of every value
+/// (not only objects) as its context.
+///
+/// Fails if `val` doesn't fit the type, or if `scale_value` can't decode the
+/// type (e.g. bit sequences it doesn't support).
+pub fn to_scale_value(
+    val: &Value,
+    type_id: u32,
+    types: &PortableRegistry,
+) -> Result<scale_value::Value<u32>, EncodeError> {
+    let mut bytes = vec![];
+    encode_value(val, type_id, types, &mut bytes)?;
+    scale_value::scale::decode_as_type(&mut &bytes[..], type_id, types).map_err(|e| EncodeError {
+        path: String::new(),
+        kind: EncodeErrorKind::Unsupported(format!("scale-value can't decode it: {}", e)),
+    })
+}
+
+/// Convert `val` as registry type `type_id`, keeping integer widths, byte
+/// strings, options and results as the decoder gives them.
+pub fn from_scale_value(
+    val: &scale_value::Value<u32>,
+    type_id: u32,
+    types: &PortableRegistry,
+) -> Result<OwnedValue, scale_value::scale::EncodeError> {
+    let mut bytes = vec![];
+    scale_value::scale::encode_as_type(val, type_id, types, &mut bytes)?;
+    Ok(ValueBuilder::parse(&bytes, type_id, types).into_owned())
+}
+
+fn with_context(value: ValueDef<u32>, context: u32) -> scale_value::Value<u32> {
+    scale_value::Value { value, context }
+}
+
+fn primitive(p: Primitive) -> scale_value::Value<u32> {
+    with_context(ValueDef::Primitive(p), NO_TYPE_ID)
+}
+
+fn composite(fields: Vec<(Cow<'_, str>, Value<'_>)>) -> Composite<u32> {
+    let fields: Vec<_> = fields
+        .into_iter()
+        .filter(|(name, _)| name != "_ty")
+        .collect();
+    let is_unnamed = fields
+        .iter()
+        .enumerate()
+        .all(|(i, (name, _))| *name == i.to_string());
+    if is_unnamed {
+        Composite::Unnamed(fields.into_iter().map(|(_, val)| val.into()).collect())
+    } else {
+        Composite::Named(
+            fields
+                .into_iter()
+                .map(|(name, val)| (name.into_owned(), val.into()))
+                .collect(),
+        )
+    }
+}
+
+impl<'scale> From<Value<'scale>> for scale_value::Value<u32> {
+    fn from(val: Value<'scale>) -> Self {
+        let bytes = |bytes: &[u8]| {
+            let bytes = bytes.iter().map(|b| primitive(Primitive::U128(*b as u128)));
+            with_context(
+                ValueDef::Composite(Composite::Unnamed(bytes.collect())),
+                BYTES,
+            )
+        };
+        let context = val.type_id().unwrap_or(NO_TYPE_ID);
+        let is_variant = val.variant().is_some();
+        match val {
+            Value::Object(mut fields) if is_variant => {
+                fields.retain(|(name, _)| name != "_ty");
+                let (name, inner) = fields.pop().unwrap();
+                let inner = match inner {
+                    Value::Object(inner) => *inner,
+                    _ => unreachable!("variant fields are always an object"),
+                };
+                let def = ValueDef::Variant(Variant {
+                    name: name.into_owned(),
+                    values: composite(inner),
+                });
+                with_context(def, context)
+            }
+            Value::Object(fields) => with_context(ValueDef::Composite(composite(*fields)), context),
+            Value::Bool(b) => primitive(Primitive::Bool(b)),
+            Value::Char(c) => primitive(Primitive::Char(c)),
+            Value::Str(s) => primitive(Primitive::String(s.into_owned())),
+            Value::Scale(b) => bytes(b),
+            Value::ScaleOwned(b) => bytes(&b),
+            Value::U8(n) => primitive(Primitive::U128(n.into())),
+            Value::U16(n) => primitive(Primitive::U128(n.into())),
+            Value::U32(n) => primitive(Primitive::U128(n.into())),
+            Value::U64(n) => primitive(Primitive::U128(n.into())),
+            Value::U128(n) => primitive(Primitive::U128(*n)),
+            Value::I8(n) => primitive(Primitive::I128(n.into())),
+            Value::I16(n) => primitive(Primitive::I128(n.into())),
+            Value::I32(n) => primitive(Primitive::I128(n.into())),
+            Value::I64(n) => primitive(Primitive::I128(n.into())),
+            Value::I128(n) => primitive(Primitive::I128(*n)),
+            Value::U256(n) => primitive(Primitive::U256(*n)),
+            Value::I256(n) => primitive(Primitive::I256(*n)),
+            Value::U256Owned(n) => primitive(Primitive::U256(*n)),
+            Value::I256Owned(n) => primitive(Primitive::I256(*n)),
+            Value::Bits(bits) => with_context(ValueDef::BitSequence(*bits), NO_TYPE_ID),
+            // The type id of an option or result isn't kept, which is what
+            // marks them out from other enums when converting back.
+            Value::None => with_context(variant("None", vec![]), NO_TYPE_ID),
+            Value::Some(val) => with_context(variant("Some", vec![(*val).into()]), NO_TYPE_ID),
+            Value::Ok(val) => with_context(variant("Ok", vec![(*val).into()]), NO_TYPE_ID),
+            Value::Err(val) => with_context(variant("Err", vec![(*val).into()]), NO_TYPE_ID),
+        }
+    }
+}
+
+fn variant(name: &str, fields: Vec<scale_value::Value<u32>>) -> ValueDef<u32> {
+    ValueDef::Variant(Variant {
+        name: name.into(),
+        values: Composite::Unnamed(fields),
+    })
+}
+
+/// The `_ty` field for `context`, if it's a type id.
+fn type_field(context: u32) -> Vec<(Cow<'static, str>, OwnedValue)> {
+    match context {
+        NO_TYPE_ID => vec![],
+        id => vec![(Cow::Borrowed("_ty"), Value::U32(id))],
+    }
+}
+
+/// Whether a variant converted without a type id is an option or result.
+fn is_wrapper(name: &str, len: usize) -> bool {
+    matches!((name, len), ("None", 0) | ("Some" | "Ok" | "Err", 1))
+}
+
+fn byte(val: &scale_value::Value<u32>) -> Option<u8> {
+    match val.value {
+        ValueDef::Primitive(Primitive::U128(n)) => n.try_into().ok(),
+        _ => None,
+    }
+}
+
+fn object(context: u32, fields: Composite<u32>) -> OwnedValue {
+    let mut out = type_field(context);
+    match fields {
+        Composite::Named(fields) => {
+            out.extend(
+                fields
+                    .into_iter()
+                    .map(|(name, val)| (Cow::Owned(name), val.into())),
+            );
+        }
+        Composite::Unnamed(fields) => {
+            out.extend(
+                fields
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, val)| (Cow::Owned(i.to_string()), val.into())),
+            );
+        }
+    }
+    Value::Object(Box::new(out))
+}
+
+impl From<scale_value::Value<u32>> for OwnedValue {
+    fn from(val: scale_value::Value<u32>) -> Self {
+        match val.value {
+            ValueDef::Composite(Composite::Unnamed(bytes))
+                if val.context == BYTES && bytes.iter().all(|b| byte(b).is_some()) =>
+            {
+                Value::ScaleOwned(Box::new(bytes.iter().filter_map(byte).collect()))
+            }
+            ValueDef::Composite(fields) => object(val.context, fields),
+            ValueDef::Variant(Variant {
+                name,
+                values: Composite::Unnamed(mut values),
+            }) if val.context == NO_TYPE_ID && is_wrapper(&name, values.len()) => {
+                match (name.as_str(), values.pop()) {
+                    ("Some", Some(inner)) => Value::Some(Box::new(inner.into())),
+                    ("Ok", Some(inner)) => Value::Ok(Box::new(inner.into())),
+                    ("Err", Some(inner)) => Value::Err(Box::new(inner.into())),
+                    _ => Value::None,
+                }
+            }
+            ValueDef::Variant(Variant { name, values }) => {
+                let mut fields = type_field(val.context);
+                fields.push((Cow::Owned(name), object(val.context, values)));
+                Value::Object(Box::new(fields))
+            }
+            ValueDef::BitSequence(bits) => Value::Bits(Box::new(bits)),
+            ValueDef::Primitive(Primitive::Bool(b)) => Value::Bool(b),
+            ValueDef::Primitive(Primitive::Char(c)) => Value::Char(c),
+            ValueDef::Primitive(Primitive::String(s)) => Value::Str(Cow::Owned(s)),
+            ValueDef::Primitive(Primitive::U128(n)) => Value::U128(Box::new(n)),
+            ValueDef::Primitive(Primitive::I128(n)) => Value::I128(Box::new(n)),
+            ValueDef::Primitive(Primitive::U256(n)) => Value::U256Owned(Box::new(n)),
+            ValueDef::Primitive(Primitive::I256(n)) => Value::I256Owned(Box::new(n)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::primitive;
+    use crate::tests::make_type_id;
+    use crate::{OwnedValue, Value, ValueBuilder};
+    use parity_scale_codec::Encode;
+    use scale_value::{Composite, Primitive, ValueDef, Variant};
+
+    #[derive(Encode, scale_info::TypeInfo)]
+    enum E {
+        #[allow(dead_code)]
+        A,
+        B {
+            flag: bool,
+            name: String,
+        },
+    }
+
+    #[derive(Encode, scale_info::TypeInfo)]
+    struct Pair(u8, u64);
+
+    #[derive(Encode, scale_info::TypeInfo)]
+    struct Outer {
+        events: Vec<E>,
+        pair: Pair,
+    }
+
+    fn decode_and_convert(encoded: &[u8]) -> scale_value::Value<u32> {
+        let (id, types) = make_type_id::<Outer>();
+        ValueBuilder::parse(encoded, id, &types).into()
+    }
+
+    #[test]
+    fn to_scale_value() {
+        let outer = Outer {
+            events: vec![E::B {
+                flag: true,
+                name: "hi".into(),
+            }],
+            pair: Pair(7, 3),
+        };
+        let val = decode_and_convert(&outer.encode());
+
+        assert_eq!(val.context, 0);
+        let fields = match val.value {
+            ValueDef::Composite(Composite::Named(fields)) => fields,
+            other => panic!("expected a named composite, got {:?}", other),
+        };
+        assert_eq!(fields[0].0, "events");
+        assert_eq!(fields[1].0, "pair");
+
+        let event = match &fields[0].1.value {
+            ValueDef::Composite(Composite::Unnamed(events)) => &events[0].value,
+            other => panic!("expected a sequence, got {:?}", other),
+        };
+        assert_eq!(
+            event,
+            &ValueDef::Variant(Variant {
+                name: "B".into(),
+                values: Composite::Named(vec![
+                    ("flag".into(), primitive(Primitive::Bool(true))),
+                    ("name".into(), primitive(Primitive::String("hi".into()))),
+                ]),
+            })
+        );
+
+        let pair = match &fields[1].1.value {
+            ValueDef::Composite(Composite::Unnamed(pair)) => pair,
+            other => panic!("expected a tuple struct, got {:?}", other),
+        };
+        assert_eq!(
+            pair,
+            &vec![primitive(Primitive::U128(7)), primitive(Primitive::U128(3))]
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let outer = Outer {
+            events: vec![E::B {
+                flag: false,
+                name: "round".into(),
+            }],
+            pair: Pair(1, 2),
+        };
+        let val = decode_and_convert(&outer.encode());
+        let owned: OwnedValue = val.clone().into();
+
+        // Variants survive the trip through scale-value and back.
+        let (name, fields) = owned.get("events.0").unwrap().variant().unwrap();
+        assert_eq!(name, "B");
+        assert_eq!(fields.get("name"), Some(&Value::Str("round".into())));
+        assert_eq!(owned.get("pair.0"), Some(&Value::U128(Box::new(1))));
+
+        let back: scale_value::Value<u32> = owned.into();
+        assert_eq!(back, val);
+    }
+
+    #[derive(Encode, scale_info::TypeInfo)]
+    struct Narrow {
+        small: u8,
+        medium: u16,
+        memo: Vec<u8>,
+        maybe: Option<u32>,
+    }
+
+    fn narrow() -> (Vec<u8>, u32, scale_info::PortableRegistry) {
+        let (id, types) = make_type_id::<Narrow>();
+        let narrow = Narrow {
+            small: 1,
+            medium: 2,
+            memo: vec![3, 4],
+            maybe: Some(5),
+        };
+        (narrow.encode(), id, types)
+    }
+
+    #[derive(Encode, scale_info::TypeInfo)]
+    struct Wide {
+        n: u128,
+        memo: Vec<u8>,
+        maybe: Option<String>,
+        res: Result<bool, String>,
+        events: Vec<E>,
+    }
+
+    #[test]
+    fn round_trip_without_registry() {
+        let (id, types) = make_type_id::<Wide>();
+        let encoded = Wide {
+            n: 1,
+            memo: vec![2, 3],
+            maybe: Some("hi".into()),
+            res: Err("no".into()),
+            events: vec![E::A],
+        }
+        .encode();
+        let val = ValueBuilder::parse(&encoded, id, &types);
+        let sv: scale_value::Value<u32> = val.clone().into();
+        let back: OwnedValue = sv.into();
+        assert_eq!(back, val);
+        assert_eq!(back.get("memo"), Some(&Value::Scale(&[2, 3])));
+        assert_eq!(back.get("res.Err"), Some(&Value::Str("no".into())));
+
+        // Objects without a type id stay without one, 256 bit numbers keep
+        // their sign and empty byte strings stay bytes.
+        let n = [0xff; 32];
+        let val = Value::Object(Box::new(vec![
+            ("a".into(), Value::I256(&n)),
+            ("b".into(), Value::U256(&n)),
+            ("c".into(), Value::Scale(&[])),
+            ("d".into(), Value::None),
+        ]));
+        let back: OwnedValue = scale_value::Value::from(val.clone()).into();
+        assert_eq!(back, val);
+
+        // Only integer widths are lost.
+        let (encoded, id, types) = narrow();
+        let val = ValueBuilder::parse(&encoded, id, &types);
+        let back: OwnedValue = scale_value::Value::from(val.clone()).into();
+        assert_eq!(back.get("small"), Some(&Value::U128(Box::new(1))));
+        assert_eq!(back.get("medium"), Some(&Value::U128(Box::new(2))));
+        assert_eq!(back.get("memo"), Some(&Value::Scale(&[3, 4])));
+        assert_eq!(
+            back.get("maybe"),
+            Some(&Value::Some(Box::new(Value::U128(Box::new(5)))))
+        );
+        assert_eq!(back.type_id(), val.type_id());
+    }
+
+    #[test]
+    fn exact_with_registry() {
+        let (encoded, id, types) = narrow();
+        let val = ValueBuilder::parse(&encoded, id, &types);
+        let sv = super::to_scale_value(&val, id, &types).unwrap();
+        let fields = match &sv.value {
+            ValueDef::Composite(Composite::Named(fields)) => fields,
+            other => panic!("expected a named composite, got {:?}", other),
+        };
+        assert_eq!(sv.context, id);
+        assert!(fields.iter().all(|(_, field)| field.context != id));
+
+        let back = super::from_scale_value(&sv, id, &types).unwrap();
+        assert_eq!(back, val);
+        assert_eq!(back.get("small"), Some(&Value::U8(1)));
+        assert_eq!(back.get("memo"), Some(&Value::Scale(&[3, 4])));
+        assert_eq!(
+            back.get("maybe"),
+            Some(&Value::Some(Box::new(Value::U32(5))))
+        );
+
+        assert!(super::from_scale_value(&primitive(Primitive::Bool(true)), id, &types).is_err());
+    }
+
+    #[test]
+    fn bits() {
+        let bits: scale_value::BitSequence = [true, false, true].into_iter().collect();
+        let val = scale_value::Value {
+            value: ValueDef::BitSequence(bits.clone()),
+            context: 3,
+        };
+        let owned: OwnedValue = val.into();
+        assert_eq!(owned, Value::Bits(Box::new(bits)));
+    }
+}
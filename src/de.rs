@@ -0,0 +1,696 @@
+//! A serde `Deserializer` that walks SCALE bytes with the registry, so user
+//! structs can be filled in directly without an intermediate [`Value`](crate::Value).
+//!
+//! Struct fields are matched by name (unknown fields are skipped) and
+//! `&'scale str` / `&'scale [u8]` fields borrow straight from the input.
+use crate::ToStr;
+use core::fmt::{Display, Formatter};
+use parity_scale_codec::{Compact, Decode};
+use scale_info::form::PortableForm;
+use scale_info::{Field, PortableRegistry, Type, TypeDef, TypeDefPrimitive};
+use serde::de::{
+    self, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess, VariantAccess,
+    Visitor,
+};
+use serde::forward_to_deserialize_any;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// Ran out of bytes part way through a value.
+    Eof,
+    /// The registry doesn't contain this type id.
+    UnknownType(u32),
+    /// No variant of the enum type has this index.
+    UnknownVariant {
+        ty: u32,
+        index: u8,
+    },
+    InvalidUtf8,
+    InvalidChar(u32),
+    /// The bytes couldn't be decoded as the given type.
+    Decode(String),
+    /// Type definitions this deserializer doesn't know how to walk.
+    Unsupported(String),
+    /// An error raised by the type being deserialized.
+    Custom(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::Eof => write!(f, "unexpected end of input"),
+            Error::UnknownType(id) => write!(f, "type {} not found in registry", id),
+            Error::UnknownVariant { ty, index } => {
+                write!(f, "type {} has no variant with index {}", ty, index)
+            }
+            Error::InvalidUtf8 => write!(f, "string is not valid utf8"),
+            Error::InvalidChar(c) => write!(f, "{:#x} is not a valid char", c),
+            Error::Decode(msg) => write!(f, "decode error: {}", msg),
+            Error::Unsupported(msg) => write!(f, "unsupported: {}", msg),
+            Error::Custom(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Custom(msg.to_string())
+    }
+}
+
+/// Deserialize a `T` from SCALE encoded `data` of registry type `type_id`.
+///
+/// ```
+/// # use parity_scale_codec::Encode;
+/// # #[derive(Encode, scale_info::TypeInfo)]
+/// # struct Call {
+/// #     to: Vec<u8>,
+/// #     amount: u128,
+/// # }
+/// # let mut registry = scale_info::Registry::new();
+/// # let type_id = registry.register_type(&scale_info::MetaType::new::<Call>()).id();
+/// # let types: scale_info::PortableRegistry = registry.into();
+/// # let encoded = Call { to: vec![1, 2], amount: 10 }.encode();
+/// # let data = &encoded[..];
+/// #[derive(serde::Deserialize)]
+/// struct Transfer<'a> {
+///     to: &'a [u8],
+///     amount: u128,
+/// }
+/// let transfer: Transfer = scale_borrow::from_bytes(data, type_id, &types)?;
+/// # assert_eq!((transfer.to, transfer.amount), (&[1, 2][..], 10));
+/// # Ok::<(), scale_borrow::de::Error>(())
+/// ```
+pub fn from_bytes<'scale, T: de::Deserialize<'scale>>(
+    data: &'scale [u8],
+    type_id: u32,
+    types: &'scale PortableRegistry,
+) -> Result<T, Error> {
+    let mut cursor = data;
+    T::deserialize(Deserializer::new(&mut cursor, type_id, types))
+}
+
+/// Deserializes one value of a registry type, advancing the cursor past it.
+pub struct Deserializer<'a, 'scale> {
+    data: &'a mut &'scale [u8],
+    ty_id: u32,
+    types: &'scale PortableRegistry,
+}
+
+impl<'a, 'scale> Deserializer<'a, 'scale> {
+    pub fn new(data: &'a mut &'scale [u8], ty_id: u32, types: &'scale PortableRegistry) -> Self {
+        Deserializer { data, ty_id, types }
+    }
+
+    fn ty(&self) -> Result<&'scale Type<PortableForm>, Error> {
+        self.types
+            .resolve(self.ty_id)
+            .ok_or(Error::UnknownType(self.ty_id))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'scale [u8], Error> {
+        if self.data.len() < len {
+            return Err(Error::Eof);
+        }
+        let (head, tail) = self.data.split_at(len);
+        *self.data = tail;
+        Ok(head)
+    }
+
+    fn decode<T: Decode>(&mut self) -> Result<T, Error> {
+        T::decode(self.data).map_err(|e| Error::Decode(e.to_string()))
+    }
+
+    fn fields(&mut self, fields: &'scale [Field<PortableForm>]) -> Fields<'_, 'scale> {
+        Fields {
+            data: self.data,
+            fields,
+            types: self.types,
+        }
+    }
+
+    /// For an `Option<T>` type, the type id of `T`.
+    fn option_inner(&self) -> Result<Option<u32>, Error> {
+        let ty = self.ty()?;
+        match ty.type_def() {
+            TypeDef::Variant(var)
+                if ty
+                    .path()
+                    .segments()
+                    .iter()
+                    .map(|s| s.make_str())
+                    .eq(["Option"]) =>
+            {
+                Ok(var
+                    .variants()
+                    .iter()
+                    .find(|v| v.name().make_str() == "Some")
+                    .and_then(|v| v.fields().first())
+                    .map(|f| f.ty().id()))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn variant(&mut self) -> Result<&'scale scale_info::Variant<PortableForm>, Error> {
+        let ty = self.ty()?;
+        match ty.type_def() {
+            TypeDef::Variant(var) => {
+                let index = self.take(1)?[0];
+                var.variants()
+                    .iter()
+                    .find(|v| v.index() == index)
+                    .ok_or(Error::UnknownVariant {
+                        ty: self.ty_id,
+                        index,
+                    })
+            }
+            other => Err(Error::Custom(format!(
+                "expected an enum, found {:?}",
+                other
+            ))),
+        }
+    }
+}
+
+impl<'a, 'scale> de::Deserializer<'scale> for Deserializer<'a, 'scale> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'scale>>(mut self, visitor: V) -> Result<V::Value, Error> {
+        let ty = self.ty()?;
+        match ty.type_def() {
+            TypeDef::Composite(composite) => {
+                // Newtype wrappers such as `AccountId32([u8; 32])` are transparent.
+                match composite.fields() {
+                    [field] if field.name().is_none() => {
+                        Deserializer::new(self.data, field.ty().id(), self.types)
+                            .deserialize_any(visitor)
+                    }
+                    fields => self.fields(fields).deserialize_any(visitor),
+                }
+            }
+            TypeDef::Variant(_) => {
+                if let Some(inner) = self.option_inner()? {
+                    return self.deserialize_option_of(inner, visitor);
+                }
+                let variant = self.variant()?;
+                visitor.visit_map(VariantMap {
+                    variant: Some(variant),
+                    data: self.data,
+                    types: self.types,
+                })
+            }
+            TypeDef::Sequence(seq) => {
+                let len = self.decode::<Compact<u32>>()?.0 as usize;
+                self.deserialize_elements(seq.type_param().id(), len, visitor)
+            }
+            TypeDef::Array(arr) => {
+                self.deserialize_elements(arr.type_param().id(), arr.len() as usize, visitor)
+            }
+            TypeDef::Tuple(tuple) => visitor.visit_seq(Elements {
+                data: self.data,
+                types: self.types,
+                ids: tuple.fields().iter().map(|f| f.id()).collect(),
+                next: 0,
+            }),
+            TypeDef::Primitive(prim) => match prim {
+                TypeDefPrimitive::Bool => visitor.visit_bool(self.decode()?),
+                TypeDefPrimitive::Char => {
+                    let c: u32 = self.decode()?;
+                    visitor.visit_char(char::from_u32(c).ok_or(Error::InvalidChar(c))?)
+                }
+                TypeDefPrimitive::Str => {
+                    let len = self.decode::<Compact<u32>>()?.0 as usize;
+                    let bytes = self.take(len)?;
+                    visitor.visit_borrowed_str(
+                        std::str::from_utf8(bytes).map_err(|_| Error::InvalidUtf8)?,
+                    )
+                }
+                TypeDefPrimitive::U8 => visitor.visit_u8(self.decode()?),
+                TypeDefPrimitive::U16 => visitor.visit_u16(self.decode()?),
+                TypeDefPrimitive::U32 => visitor.visit_u32(self.decode()?),
+                TypeDefPrimitive::U64 => visitor.visit_u64(self.decode()?),
+                TypeDefPrimitive::U128 => visitor.visit_u128(self.decode()?),
+                TypeDefPrimitive::I8 => visitor.visit_i8(self.decode()?),
+                TypeDefPrimitive::I16 => visitor.visit_i16(self.decode()?),
+                TypeDefPrimitive::I32 => visitor.visit_i32(self.decode()?),
+                TypeDefPrimitive::I64 => visitor.visit_i64(self.decode()?),
+                TypeDefPrimitive::I128 => visitor.visit_i128(self.decode()?),
+                TypeDefPrimitive::U256 | TypeDefPrimitive::I256 => {
+                    visitor.visit_borrowed_bytes(self.take(32)?)
+                }
+            },
+            TypeDef::Compact(inner) => {
+                let inner = self
+                    .types
+                    .resolve(inner.type_param().id())
+                    .ok_or(Error::UnknownType(inner.type_param().id()))?;
+                match inner.type_def() {
+                    TypeDef::Primitive(TypeDefPrimitive::U8) => {
+                        visitor.visit_u8(self.decode::<Compact<u8>>()?.0)
+                    }
+                    TypeDef::Primitive(TypeDefPrimitive::U16) => {
+                        visitor.visit_u16(self.decode::<Compact<u16>>()?.0)
+                    }
+                    TypeDef::Primitive(TypeDefPrimitive::U32) => {
+                        visitor.visit_u32(self.decode::<Compact<u32>>()?.0)
+                    }
+                    TypeDef::Primitive(TypeDefPrimitive::U64) => {
+                        visitor.visit_u64(self.decode::<Compact<u64>>()?.0)
+                    }
+                    TypeDef::Primitive(TypeDefPrimitive::U128) => {
+                        visitor.visit_u128(self.decode::<Compact<u128>>()?.0)
+                    }
+                    other => Err(Error::Unsupported(format!("compact {:?}", other))),
+                }
+            }
+            TypeDef::BitSequence(_) => self.deserialize_bits(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'scale>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.option_inner()? {
+            Some(inner) => self.deserialize_option_of(inner, visitor),
+            // A plain value on chain can still go into an `Option` field.
+            None => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'scale>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let ty = self.ty()?;
+        match ty.type_def() {
+            TypeDef::Composite(composite) if composite.fields().len() == 1 => {
+                let id = composite.fields()[0].ty().id();
+                visitor.visit_newtype_struct(Deserializer::new(self.data, id, self.types))
+            }
+            _ => visitor.visit_newtype_struct(self),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'scale>>(mut self, visitor: V) -> Result<V::Value, Error> {
+        // Unlike `deserialize_any`, don't unwrap single field tuple structs.
+        if let TypeDef::Composite(composite) = self.ty()?.type_def() {
+            return self.fields(composite.fields()).deserialize_any(visitor);
+        }
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_tuple<V: Visitor<'scale>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'scale>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'scale>>(
+        mut self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let variant = self.variant()?;
+        visitor.visit_enum(Enum {
+            variant,
+            data: self.data,
+            types: self.types,
+        })
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'scale>>(self, visitor: V) -> Result<V::Value, Error> {
+        // Still has to walk the value to move past it.
+        self.deserialize_any(de::IgnoredAny)?;
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        <W: Visitor<'scale>>
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct map struct identifier
+    }
+}
+
+impl<'a, 'scale> Deserializer<'a, 'scale> {
+    fn deserialize_option_of<V: Visitor<'scale>>(
+        mut self,
+        inner: u32,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.take(1)?[0] {
+            0 => visitor.visit_none(),
+            1 => visitor.visit_some(Deserializer::new(self.data, inner, self.types)),
+            index => Err(Error::UnknownVariant {
+                ty: self.ty_id,
+                index,
+            }),
+        }
+    }
+
+    fn deserialize_elements<V: Visitor<'scale>>(
+        mut self,
+        elem: u32,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let elem_ty = self.types.resolve(elem).ok_or(Error::UnknownType(elem))?;
+        if *elem_ty.type_def() == TypeDef::Primitive(TypeDefPrimitive::U8) {
+            visitor.visit_borrowed_bytes(self.take(len)?)
+        } else {
+            visitor.visit_seq(Elements {
+                data: self.data,
+                types: self.types,
+                ids: vec![elem; len],
+                next: 0,
+            })
+        }
+    }
+
+    #[cfg(feature = "bitvec")]
+    fn deserialize_bits<V: Visitor<'scale>>(self, visitor: V) -> Result<V::Value, Error> {
+        let bits = scale_value::BitSequence::decode(self.data)
+            .map_err(|e| Error::Decode(e.to_string()))?;
+        visitor.visit_seq(de::value::SeqDeserializer::new(bits.iter()))
+    }
+
+    #[cfg(not(feature = "bitvec"))]
+    fn deserialize_bits<V: Visitor<'scale>>(self, _visitor: V) -> Result<V::Value, Error> {
+        Err(Error::Unsupported(
+            "bit sequences need the bitvec feature".into(),
+        ))
+    }
+}
+
+/// The fields of a composite or enum variant: a map if they're named,
+/// otherwise a sequence (or unit if there are none).
+struct Fields<'a, 'scale> {
+    data: &'a mut &'scale [u8],
+    fields: &'scale [Field<PortableForm>],
+    types: &'scale PortableRegistry,
+}
+
+impl<'a, 'scale> de::Deserializer<'scale> for Fields<'a, 'scale> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'scale>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.fields.first() {
+            None => visitor.visit_unit(),
+            Some(field) if field.name().is_some() => visitor.visit_map(self),
+            Some(_) => visitor.visit_seq(Elements {
+                data: self.data,
+                types: self.types,
+                ids: self.fields.iter().map(|f| f.ty().id()).collect(),
+                next: 0,
+            }),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        <W: Visitor<'scale>>
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+impl<'a, 'scale> MapAccess<'scale> for Fields<'a, 'scale> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'scale>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        match self.fields.first() {
+            Some(field) => {
+                let name: &'scale str = field.name().map(|n| n.make_str()).unwrap_or_default();
+                seed.deserialize(de::value::BorrowedStrDeserializer::new(name))
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'scale>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let (field, rest) = self.fields.split_first().ok_or(Error::Eof)?;
+        self.fields = rest;
+        seed.deserialize(Deserializer::new(self.data, field.ty().id(), self.types))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.fields.len())
+    }
+}
+
+/// A run of values of known types, one after the other.
+struct Elements<'a, 'scale> {
+    data: &'a mut &'scale [u8],
+    types: &'scale PortableRegistry,
+    ids: Vec<u32>,
+    next: usize,
+}
+
+impl<'a, 'scale> SeqAccess<'scale> for Elements<'a, 'scale> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'scale>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        match self.ids.get(self.next) {
+            Some(id) => {
+                self.next += 1;
+                seed.deserialize(Deserializer::new(self.data, *id, self.types))
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.ids.len() - self.next)
+    }
+}
+
+/// An enum seen without a hint from the target type: `{"Variant": fields}`.
+struct VariantMap<'a, 'scale> {
+    variant: Option<&'scale scale_info::Variant<PortableForm>>,
+    data: &'a mut &'scale [u8],
+    types: &'scale PortableRegistry,
+}
+
+impl<'a, 'scale> MapAccess<'scale> for VariantMap<'a, 'scale> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'scale>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        match self.variant {
+            Some(variant) => {
+                let name: &'scale str = variant.name().make_str();
+                seed.deserialize(de::value::BorrowedStrDeserializer::new(name))
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'scale>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let variant = self.variant.take().ok_or(Error::Eof)?;
+        seed.deserialize(Fields {
+            data: self.data,
+            fields: variant.fields(),
+            types: self.types,
+        })
+    }
+}
+
+/// An enum deserialized into a Rust enum.
+struct Enum<'a, 'scale> {
+    variant: &'scale scale_info::Variant<PortableForm>,
+    data: &'a mut &'scale [u8],
+    types: &'scale PortableRegistry,
+}
+
+impl<'a, 'scale> EnumAccess<'scale> for Enum<'a, 'scale> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'scale>>(self, seed: V) -> Result<(V::Value, Self), Error> {
+        let name: &'scale str = self.variant.name().make_str();
+        let name = seed.deserialize(name.into_deserializer())?;
+        Ok((name, self))
+    }
+}
+
+impl<'a, 'scale> Enum<'a, 'scale> {
+    fn fields(self) -> Fields<'a, 'scale> {
+        Fields {
+            data: self.data,
+            fields: self.variant.fields(),
+            types: self.types,
+        }
+    }
+}
+
+impl<'a, 'scale> VariantAccess<'scale> for Enum<'a, 'scale> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        // Skip over any fields the Rust side doesn't care about.
+        de::Deserializer::deserialize_any(self.fields(), de::IgnoredAny).map(|_| ())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'scale>>(self, seed: T) -> Result<T::Value, Error> {
+        match self.variant.fields() {
+            [field] => seed.deserialize(Deserializer::new(self.data, field.ty().id(), self.types)),
+            _ => seed.deserialize(self.fields()),
+        }
+    }
+
+    fn tuple_variant<V: Visitor<'scale>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_any(self.fields(), visitor)
+    }
+
+    fn struct_variant<V: Visitor<'scale>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_any(self.fields(), visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::make_type_id;
+    use parity_scale_codec::Encode;
+    use serde::Deserialize;
+
+    #[derive(Encode, scale_info::TypeInfo)]
+    enum Call {
+        #[allow(dead_code)]
+        Remark(Vec<u8>),
+        Transfer {
+            dest: [u8; 4],
+            value: u128,
+        },
+    }
+
+    #[derive(Encode, scale_info::TypeInfo)]
+    struct Extrinsic {
+        signer: Option<[u8; 4]>,
+        nonce: u32,
+        memo: String,
+        call: Call,
+        tips: Vec<u64>,
+        pair: (u8, bool),
+    }
+
+    fn encoded() -> Vec<u8> {
+        Extrinsic {
+            signer: Some([1, 2, 3, 4]),
+            nonce: 7,
+            memo: "hi".into(),
+            call: Call::Transfer {
+                dest: [9; 4],
+                value: 1_000,
+            },
+            tips: vec![1, 2],
+            pair: (3, true),
+        }
+        .encode()
+    }
+
+    #[test]
+    fn borrowed_struct() {
+        // Only the fields we want, in any order and with looser types.
+        #[derive(Deserialize, Debug, PartialEq)]
+        enum MyCall<'a> {
+            Remark(&'a [u8]),
+            Transfer { value: u128, dest: &'a [u8] },
+        }
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Mine<'a> {
+            memo: &'a str,
+            call: MyCall<'a>,
+            nonce: u64,
+            signer: Option<&'a [u8]>,
+            pair: (u8, bool),
+            tips: Vec<u64>,
+        }
+
+        let data = encoded();
+        let (id, types) = make_type_id::<Extrinsic>();
+        let mine: Mine = from_bytes(&data, id, &types).unwrap();
+        assert_eq!(
+            mine,
+            Mine {
+                memo: "hi",
+                call: MyCall::Transfer {
+                    value: 1_000,
+                    dest: &[9; 4]
+                },
+                nonce: 7,
+                signer: Some(&[1, 2, 3, 4]),
+                pair: (3, true),
+                tips: vec![1, 2],
+            }
+        );
+        // Zero copy.
+        assert!(data.as_ptr_range().contains(&mine.memo.as_ptr()));
+    }
+
+    #[test]
+    fn skips_unknown_fields() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Tail {
+            tips: Vec<u64>,
+        }
+        let (id, types) = make_type_id::<Extrinsic>();
+        let tail: Tail = from_bytes(&encoded(), id, &types).unwrap();
+        assert_eq!(tail.tips, vec![1, 2]);
+    }
+
+    #[test]
+    fn errors() {
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Mine {
+            nonce: u8,
+        }
+        let (id, types) = make_type_id::<Extrinsic>();
+        let data = encoded();
+        assert_eq!(
+            from_bytes::<Mine>(&data[..3], id, &types).unwrap_err(),
+            Error::Eof
+        );
+        assert!(matches!(
+            from_bytes::<Mine>(&data, 999, &types),
+            Err(Error::UnknownType(999))
+        ));
+        // Value doesn't fit in the narrower Rust type.
+        let (id, types) = make_type_id::<u32>();
+        let big = 300u32.encode();
+        assert!(matches!(
+            from_bytes::<u8>(&big, id, &types),
+            Err(Error::Custom(_))
+        ));
+    }
+}
@@ -0,0 +1,1565 @@
+//! Structs decoded from a few paths into a value, as declared with
+//! [`descale!`](crate::descale) or `#[derive(Descale)]`.
+//!
+//! A path that doesn't exist is just never visited, so a typo or a runtime
+//! upgrade that renames a field silently leaves the field at its default.
+//! [`Descale::bind`] checks every path against the registry once, up front,
+//! and hands back a [`Bound`] decoder for the parses that follow.
+//!
+//! ```
+//! # use parity_scale_codec::Encode;
+//! # use scale_borrow::{descale, Descale};
+//! # #[derive(Encode, scale_info::TypeInfo)]
+//! # struct Call {
+//! #     dest: [u8; 4],
+//! #     amount: u128,
+//! # }
+//! # let mut registry = scale_info::Registry::new();
+//! # let call_ty = registry.register_type(&scale_info::MetaType::new::<Call>()).id();
+//! # let registry: scale_info::PortableRegistry = registry.into();
+//! # let calls = vec![Call { dest: [1; 4], amount: 5 }.encode()];
+//! # descale! {
+//! #     struct Transfer<'scale> {
+//! #         #[path("amount")]
+//! #         amount: u128,
+//! #     }
+//! # }
+//! let transfers = Transfer::bind(call_ty, &registry)?;
+//! for data in &calls {
+//!     let transfer = transfers.parse(data);
+//! #   assert_eq!(transfer.amount, 5);
+//! }
+//! # Ok::<(), scale_borrow::descale::BindError>(())
+//! ```
+use crate::borrow_decode::BorrowDecode;
+use crate::{skeleton_decode, PathSegment, ToStr, VisitScale};
+use core::any::TypeId;
+use core::fmt::{Display, Formatter};
+use core::marker::PhantomData;
+use scale_info::form::PortableForm;
+use scale_info::interner::UntrackedSymbol;
+use scale_info::{Field, PortableRegistry, Type, TypeDef, TypeDefPrimitive};
+#[cfg(feature = "smallvec")]
+use smallvec::{Array, SmallVec};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+
+/// A struct whose fields are decoded from paths into a value, or an enum
+/// with an arm of such fields per variant.
+///
+/// Implemented by [`descale!`](crate::descale),
+/// [`descale_enum!`](crate::descale_enum) and `#[derive(Descale)]`.
+pub trait Descale<'scale>: VisitScale<'scale> + Sized {
+    /// The value before anything is decoded into it.
+    fn empty() -> Self;
+
+    /// The paths fields are decoded from.
+    fn paths() -> Vec<DescalePath>;
+
+    /// [`VisitScale::visit`], noting in `seen` which path it was at and
+    /// returning the error from a `try_with` converter, which the walk can
+    /// then be stopped for. Visiting drops it and leaves the field alone.
+    fn try_visit(
+        &mut self,
+        path: &[(PathSegment<'scale>, u32)],
+        data: &'scale [u8],
+        ty: &'scale Type<PortableForm>,
+        types: &'scale PortableRegistry,
+        seen: Seen<'_>,
+    ) -> Result<(), ParseError>;
+
+    /// [`VisitScale::variant`], noting in `seen` which enums a variant was
+    /// seen of.
+    fn try_variant(
+        &mut self,
+        path: &[(PathSegment<'scale>, u32)],
+        data: &'scale [u8],
+        seen: Seen<'_>,
+    );
+
+    /// What has to be in the data to parse, worked out from
+    /// [`Descale::paths`]. The derive caches it for types without type
+    /// parameters.
+    #[doc(hidden)]
+    fn requirements() -> Cow<'static, Requirements> {
+        Cow::Owned(Requirements::new(Self::paths()))
+    }
+
+    /// Decode from `data`, which holds a value of type `type_id` in `types`.
+    ///
+    /// # Panics
+    ///
+    /// If a required path isn't in the data or a `try_with` converter fails;
+    /// see [`Descale::try_parse`].
+    fn parse(data: &'scale [u8], type_id: u32, types: &'scale PortableRegistry) -> Self {
+        match Self::try_parse(data, type_id, types) {
+            Ok(slf) => slf,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Decode from `data`, failing if a required path isn't in it (for
+    /// example because it goes through an enum variant that isn't the one
+    /// present) or a `try_with` converter returns an error.
+    fn try_parse(
+        data: &'scale [u8],
+        type_id: u32,
+        types: &'scale PortableRegistry,
+    ) -> Result<Self, ParseError> {
+        parse_with(data, type_id, types, &Self::requirements())
+    }
+
+    /// Check that every path exists in type `type_id` and leads to a type
+    /// its field can be decoded from.
+    fn bind(
+        type_id: u32,
+        types: &'scale PortableRegistry,
+    ) -> Result<Bound<'scale, Self>, BindError> {
+        let paths = Self::paths();
+        for path in &paths {
+            path.check(type_id, types)?;
+        }
+        Ok(Bound {
+            type_id,
+            types,
+            required: Requirements::new(paths),
+            _marker: PhantomData,
+        })
+    }
+}
+
+/// The type id argument of the `parse` that [`descale!`](crate::descale) and
+/// `#[derive(Descale)]` generate: either the `u32` id or the
+/// `UntrackedSymbol` that `Registry::register_type` returns.
+pub trait IntoTypeId {
+    fn into_type_id(self) -> u32;
+}
+
+impl IntoTypeId for u32 {
+    fn into_type_id(self) -> u32 {
+        self
+    }
+}
+
+impl IntoTypeId for UntrackedSymbol<TypeId> {
+    fn into_type_id(self) -> u32 {
+        self.id()
+    }
+}
+
+/// How a field of a [`Descale`] struct stores what's decoded at its path.
+///
+/// Anything [`BorrowDecode`] holds a single value, overwritten if the path
+/// matches more than once. Collections keep every match, for paths with `*`
+/// segments: `Vec`, `VecDeque`, [`DescaleIter`] (which decodes lazily) and,
+/// with the `smallvec` feature, `SmallVec`.
+pub trait DescaleField<'scale> {
+    /// Whether every match is kept.
+    const MANY: bool;
+
+    /// [`BorrowDecode::accepts`] for the decoded values.
+    fn accepts(ty: &Type<PortableForm>, types: &PortableRegistry) -> bool;
+
+    /// Store the value in `data`.
+    fn decode_into(&mut self, data: &'scale [u8]);
+
+    /// How many of its struct's [`Descale::paths`] the field has: a nested
+    /// struct's own paths, or the one.
+    const PATHS: usize = 1;
+
+    /// For a nested [`Descale`] struct, its paths, relative to where it's
+    /// mounted.
+    fn nested() -> Option<fn() -> Vec<DescalePath>> {
+        None
+    }
+
+    /// For a nested [`Descale`] struct, visit a value at `rest` below where
+    /// it's mounted, as [`Descale::try_visit`]. Nothing for anything else.
+    fn visit_below(
+        &mut self,
+        _rest: &[(PathSegment<'scale>, u32)],
+        _data: &'scale [u8],
+        _ty: &'scale Type<PortableForm>,
+        _types: &'scale PortableRegistry,
+        _seen: Seen<'_>,
+    ) -> Result<(), ParseError> {
+        Ok(())
+    }
+
+    /// For a nested [`Descale`] struct, [`Descale::try_variant`] at `rest`
+    /// below where it's mounted.
+    fn variant_below(
+        &mut self,
+        _rest: &[(PathSegment<'scale>, u32)],
+        _data: &'scale [u8],
+        _seen: Seen<'_>,
+    ) {
+    }
+}
+
+impl<'scale, T: BorrowDecode<'scale>> DescaleField<'scale> for T {
+    const MANY: bool = false;
+
+    fn accepts(ty: &Type<PortableForm>, types: &PortableRegistry) -> bool {
+        T::accepts(ty, types)
+    }
+
+    fn decode_into(&mut self, data: &'scale [u8]) {
+        *self = T::borrow_decode(data);
+    }
+}
+
+macro_rules! impl_descale_field_many {
+    ($($c:ident<$g:ident $(: $bound:path)?> => $item:ty;)+) => {
+        $(
+            impl<'scale, $g $(: $bound)?> DescaleField<'scale> for $c<$g>
+            where
+                $item: BorrowDecode<'scale>,
+            {
+                const MANY: bool = true;
+
+                fn accepts(ty: &Type<PortableForm>, types: &PortableRegistry) -> bool {
+                    <$item as BorrowDecode<'scale>>::accepts(ty, types)
+                }
+
+                fn decode_into(&mut self, data: &'scale [u8]) {
+                    self.extend(Some(<$item as BorrowDecode<'scale>>::borrow_decode(data)));
+                }
+            }
+        )+
+    };
+}
+
+impl_descale_field_many! {
+    Vec<T> => T;
+    VecDeque<T> => T;
+}
+
+#[cfg(feature = "smallvec")]
+impl_descale_field_many! {
+    SmallVec<A: Array> => A::Item;
+}
+
+/// A collection field that keeps where each match is in the input and only
+/// decodes it when iterated over, so values that aren't needed are never
+/// decoded. It can be iterated again by cloning it first.
+pub struct DescaleIter<'scale, T> {
+    matches: Vec<&'scale [u8]>,
+    next: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<'scale, T> DescaleIter<'scale, T> {
+    /// The encoded values not yet iterated over.
+    pub fn as_slices(&self) -> &[&'scale [u8]] {
+        &self.matches[self.next..]
+    }
+}
+
+impl<T> Default for DescaleIter<'_, T> {
+    fn default() -> Self {
+        DescaleIter {
+            matches: Vec::new(),
+            next: 0,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for DescaleIter<'_, T> {
+    fn clone(&self) -> Self {
+        DescaleIter {
+            matches: self.matches.clone(),
+            next: self.next,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> core::fmt::Debug for DescaleIter<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_list().entries(self.as_slices()).finish()
+    }
+}
+
+impl<'scale, T: BorrowDecode<'scale>> Iterator for DescaleIter<'scale, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let data = self.matches.get(self.next)?;
+        self.next += 1;
+        Some(T::borrow_decode(data))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.matches.len() - self.next;
+        (len, Some(len))
+    }
+}
+
+impl<'scale, T: BorrowDecode<'scale>> ExactSizeIterator for DescaleIter<'scale, T> {}
+
+impl<'scale, T: BorrowDecode<'scale>> DescaleField<'scale> for DescaleIter<'scale, T> {
+    const MANY: bool = true;
+
+    fn accepts(ty: &Type<PortableForm>, types: &PortableRegistry) -> bool {
+        T::accepts(ty, types)
+    }
+
+    fn decode_into(&mut self, data: &'scale [u8]) {
+        self.matches.push(data);
+    }
+}
+
+/// Whether the walker's `path` matches a dotted `pattern`, where a `*`
+/// segment matches anything.
+pub fn path_matches(path: &[(PathSegment, u32)], pattern: &str) -> bool {
+    strip_prefix(path, pattern).is_some_and(<[_]>::is_empty)
+}
+
+/// What's left of the walker's `path` after a dotted `pattern` that matches
+/// its start, as [`path_matches`].
+pub fn strip_prefix<'a, 'b>(
+    path: &'a [(PathSegment<'b>, u32)],
+    pattern: &str,
+) -> Option<&'a [(PathSegment<'b>, u32)]> {
+    let mut path = path.iter();
+    for want in pattern.split('.') {
+        let (seg, _) = path.next()?;
+        if want != "*" && *seg != want {
+            return None;
+        }
+    }
+    Some(path.as_slice())
+}
+
+/// Pass a value the walker visits at `path` on to `field` if it's at or
+/// below the field's `pattern`.
+pub fn visit_field<'scale, F: DescaleField<'scale>>(
+    field: &mut F,
+    pattern: &str,
+    path: &[(PathSegment<'scale>, u32)],
+    data: &'scale [u8],
+    ty: &'scale Type<PortableForm>,
+    types: &'scale PortableRegistry,
+) -> Result<(), ParseError> {
+    match strip_prefix(path, pattern) {
+        Some(rest) => visit_at(field, rest, data, ty, types),
+        None => Ok(()),
+    }
+}
+
+/// Pass a [`VisitScale::variant`] at `path` on to `field` if it's below the
+/// field's `pattern`.
+pub fn variant_field<'scale, F: DescaleField<'scale>>(
+    field: &mut F,
+    pattern: &str,
+    path: &[(PathSegment<'scale>, u32)],
+    data: &'scale [u8],
+) {
+    if let Some(rest) = strip_prefix(path, pattern) {
+        variant_at(field, rest, data);
+    }
+}
+
+/// Pass a value the walker visits `rest` below the end of the field's path
+/// on to `field`: decode it if `rest` is empty, else hand it to a nested
+/// struct.
+pub fn visit_at<'scale, F: DescaleField<'scale>>(
+    field: &mut F,
+    rest: &[(PathSegment<'scale>, u32)],
+    data: &'scale [u8],
+    ty: &'scale Type<PortableForm>,
+    types: &'scale PortableRegistry,
+) -> Result<(), ParseError> {
+    match rest {
+        [] => {
+            field.decode_into(data);
+            Ok(())
+        }
+        rest => field.visit_below(rest, data, ty, types, Seen::none()),
+    }
+}
+
+/// Pass a [`VisitScale::variant`] `rest` below the end of the field's path
+/// on to `field`.
+pub fn variant_at<'scale, F: DescaleField<'scale>>(
+    field: &mut F,
+    rest: &[(PathSegment<'scale>, u32)],
+    data: &'scale [u8],
+) {
+    if !rest.is_empty() {
+        field.variant_below(rest, data, Seen::none());
+    }
+}
+
+/// Fields declared with a converter, `with = f` or `try_with = f`: the value
+/// at the field's path is borrow-decoded as whatever `f` takes (`&[u8]` for
+/// the raw bytes) and the field set to what it returns.
+pub mod convert {
+    use super::{ParseError, ParseErrorKind};
+    use crate::borrow_decode::BorrowDecode;
+    use crate::PathSegment;
+
+    /// Set `field` to `with` applied to the value at `path`. This can't fail,
+    /// but returns a `Result` to be called the same way as [`try_with`].
+    pub fn with<'scale, In: BorrowDecode<'scale>, Out>(
+        field: &mut Out,
+        _path: &str,
+        with: impl FnOnce(In) -> Out,
+        rest: &[(PathSegment<'scale>, u32)],
+        data: &'scale [u8],
+    ) -> Result<(), ParseError> {
+        if rest.is_empty() {
+            *field = with(In::borrow_decode(data));
+        }
+        Ok(())
+    }
+
+    /// As [`with`], but an error leaves the field alone and makes
+    /// [`Descale::try_parse`](super::Descale::try_parse) fail.
+    pub fn try_with<'scale, In, Out, E>(
+        field: &mut Out,
+        path: &str,
+        with: impl FnOnce(In) -> Result<Out, E>,
+        rest: &[(PathSegment<'scale>, u32)],
+        data: &'scale [u8],
+    ) -> Result<(), ParseError>
+    where
+        In: BorrowDecode<'scale>,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        if !rest.is_empty() {
+            return Ok(());
+        }
+        *field = with(In::borrow_decode(data)).map_err(|err| ParseError {
+            path: path.to_string(),
+            kind: ParseErrorKind::Convert(err.into()),
+        })?;
+        Ok(())
+    }
+}
+
+/// A path a field is decoded from.
+#[derive(Clone, Debug)]
+pub struct DescalePath {
+    /// Dotted path, e.g. `outer.0.val`.
+    pub path: Cow<'static, str>,
+    /// The field's Rust type, for error messages.
+    pub field_type: &'static str,
+    /// [`DescaleField::accepts`] for the field's type.
+    pub accepts: fn(&Type<PortableForm>, &PortableRegistry) -> bool,
+    /// [`DescaleField::MANY`] for the field's type.
+    pub many: bool,
+    /// Parsing fails if the path isn't visited.
+    pub required: bool,
+    /// [`DescaleField::nested`] for the field's type.
+    pub nested: Option<fn() -> Vec<DescalePath>>,
+    /// The path names an enum variant, which only has to exist.
+    pub variant: bool,
+}
+
+impl DescalePath {
+    /// A path for a field of type `T`.
+    pub fn new<'scale, T: DescaleField<'scale>>(path: &'static str) -> Self {
+        DescalePath {
+            path: path.into(),
+            field_type: core::any::type_name::<T>(),
+            accepts: T::accepts,
+            many: T::MANY,
+            required: false,
+            nested: T::nested(),
+            variant: false,
+        }
+    }
+
+    /// A path for a field set by the converter `with` (see [`convert`]),
+    /// which takes an `In`.
+    pub fn converted<'scale, In: BorrowDecode<'scale>, Out>(
+        path: &'static str,
+        _with: impl FnOnce(In) -> Out,
+    ) -> Self {
+        DescalePath {
+            path: path.into(),
+            field_type: core::any::type_name::<In>(),
+            accepts: In::accepts,
+            many: false,
+            required: false,
+            nested: None,
+            variant: false,
+        }
+    }
+
+    /// The path to an enum variant a [`Descale`] enum has an arm for.
+    pub fn variant(path: &'static str) -> Self {
+        DescalePath {
+            path: path.into(),
+            field_type: "",
+            accepts: |_, _| false,
+            many: false,
+            required: false,
+            nested: None,
+            variant: true,
+        }
+    }
+
+    /// Replace each nested struct's path with its own paths below it. A
+    /// required nested struct has all its paths required. A required nested
+    /// enum has its variants required, which any one of them seen satisfies,
+    /// but not the fields of its arms, as only one arm is ever there.
+    pub fn flatten(paths: Vec<DescalePath>) -> Vec<DescalePath> {
+        paths
+            .into_iter()
+            .flat_map(|mount| match mount.nested {
+                Some(nested) => {
+                    let inner = nested();
+                    let arms: Vec<String> = inner
+                        .iter()
+                        .filter(|p| p.variant)
+                        .map(|p| format!("{}.", p.path))
+                        .collect();
+                    inner
+                        .into_iter()
+                        .map(|inner| {
+                            let in_arm =
+                                arms.iter().any(|arm| inner.path.starts_with(arm.as_str()));
+                            DescalePath {
+                                path: format!("{}.{}", mount.path, inner.path).into(),
+                                required: inner.required || (mount.required && !in_arm),
+                                ..inner
+                            }
+                        })
+                        .collect()
+                }
+                None => vec![mount],
+            })
+            .collect()
+    }
+
+    /// Make parsing fail when the path isn't in the data.
+    pub fn required(self) -> Self {
+        DescalePath {
+            required: true,
+            ..self
+        }
+    }
+
+    fn check(&self, type_id: u32, types: &PortableRegistry) -> Result<(), BindError> {
+        let err = |kind| BindError {
+            path: self.path.to_string(),
+            kind,
+        };
+        if !self.many && self.path.split('.').any(|seg| seg == "*") {
+            return Err(err(BindErrorKind::NotACollection {
+                field_type: self.field_type,
+            }));
+        }
+        let nodes = resolve(&self.path, type_id, types).map_err(err)?;
+        if self.variant {
+            return match nodes.iter().all(|node| matches!(node, Node::Fields(_))) {
+                true => Ok(()),
+                false => Err(err(BindErrorKind::NotAVariant)),
+            };
+        }
+        for node in nodes {
+            let ty = match node {
+                Node::Type(ty) if is_leaf(ty, types) => ty,
+                _ => return Err(err(BindErrorKind::NotALeaf)),
+            };
+            if !(self.accepts)(ty, types) {
+                return Err(err(BindErrorKind::TypeMismatch {
+                    field_type: self.field_type,
+                    found: describe(ty, types),
+                }));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A [`Descale`] struct checked against a type, from [`Descale::bind`].
+///
+/// It keeps the registry borrowed for `'scale`, which the decoded fields also
+/// borrow from, so the data passed to [`Bound::parse`] must live as long as
+/// the registry.
+pub struct Bound<'scale, T> {
+    type_id: u32,
+    types: &'scale PortableRegistry,
+    required: Requirements,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<'scale, T: Descale<'scale>> Bound<'scale, T> {
+    /// Decode from `data`, which holds a value of the bound type.
+    ///
+    /// # Panics
+    ///
+    /// As [`Descale::parse`].
+    pub fn parse(&self, data: &'scale [u8]) -> T {
+        match self.try_parse(data) {
+            Ok(slf) => slf,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// As [`Descale::try_parse`].
+    pub fn try_parse(&self, data: &'scale [u8]) -> Result<T, ParseError> {
+        parse_with(data, self.type_id, self.types, &self.required)
+    }
+}
+
+impl<T> Bound<'_, T> {
+    /// The type id this was bound to.
+    pub fn type_id(&self) -> u32 {
+        self.type_id
+    }
+}
+
+impl<T> Clone for Bound<'_, T> {
+    fn clone(&self) -> Self {
+        Bound {
+            type_id: self.type_id,
+            types: self.types,
+            required: self.required.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Why a path doesn't fit the type, and which path it was.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BindError {
+    /// The dotted path as declared, e.g. `outer.0.val`.
+    pub path: String,
+    pub kind: BindErrorKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BindErrorKind {
+    /// The registry doesn't contain this type id.
+    UnknownType(u32),
+    /// Nothing called `segment` where the path expects it.
+    NotFound { segment: String },
+    /// The path stops at a struct, enum, variant or collection rather than
+    /// a value the walker hands to the field.
+    NotALeaf,
+    /// The field can't be decoded from the type found at the path.
+    TypeMismatch {
+        field_type: &'static str,
+        found: String,
+    },
+    /// The path has a `*`, so can match many times, but the field holds a
+    /// single value.
+    NotACollection { field_type: &'static str },
+    /// A [`Descale`] enum's arm is for something that isn't an enum variant.
+    NotAVariant,
+}
+
+impl Display for BindError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "at {}: ", self.path)?;
+        match &self.kind {
+            BindErrorKind::UnknownType(id) => write!(f, "type {} not found in registry", id),
+            BindErrorKind::NotFound { segment } => write!(f, "no {} in the type", segment),
+            BindErrorKind::NotALeaf => write!(f, "path ends before reaching a value"),
+            BindErrorKind::TypeMismatch { field_type, found } => {
+                write!(f, "can't decode {} from {}", field_type, found)
+            }
+            BindErrorKind::NotACollection { field_type } => {
+                write!(f, "path has a `*` but {} holds a single value", field_type)
+            }
+            BindErrorKind::NotAVariant => write!(f, "not an enum variant"),
+        }
+    }
+}
+
+impl std::error::Error for BindError {}
+
+/// Why a [`Descale`] struct couldn't be parsed, and which path it was.
+#[derive(Debug)]
+pub struct ParseError {
+    /// The dotted path as declared, e.g. `outer.0.val`.
+    pub path: String,
+    pub kind: ParseErrorKind,
+}
+
+#[derive(Debug)]
+pub enum ParseErrorKind {
+    /// A required path wasn't in the data.
+    Missing,
+    /// A `try_with` converter failed, with its error.
+    Convert(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "at {}: ", self.path)?;
+        match &self.kind {
+            ParseErrorKind::Missing => write!(f, "required path not present"),
+            ParseErrorKind::Convert(err) => write!(f, "conversion failed: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ParseErrorKind::Missing => None,
+            ParseErrorKind::Convert(err) => Some(&**err),
+        }
+    }
+}
+
+/// Which of a [`Descale`] type's [`Descale::paths`] a parse has visited, by
+/// index, from the generated code that matches them. A nested struct's paths
+/// start at its field's index, and an enum is marked at its first variant's
+/// index whichever variant is there.
+pub struct Seen<'a> {
+    paths: &'a mut [bool],
+    at: usize,
+}
+
+impl Seen<'_> {
+    /// Nowhere to note anything, for a visit outside a parse.
+    pub fn none() -> Seen<'static> {
+        Seen {
+            paths: &mut [],
+            at: 0,
+        }
+    }
+
+    /// Note the path at `index` as visited.
+    pub fn mark(&mut self, index: usize) {
+        if let Some(seen) = self.paths.get_mut(self.at + index) {
+            *seen = true;
+        }
+    }
+
+    /// The paths from `index` on, for a nested struct mounted there.
+    pub fn at(&mut self, index: usize) -> Seen<'_> {
+        Seen {
+            paths: &mut *self.paths,
+            at: self.at + index,
+        }
+    }
+}
+
+/// What has to be in the data for a [`Descale`] type to parse: the indexes
+/// [`Seen`] has to have marked, with the paths to report if they're missing.
+#[doc(hidden)]
+#[derive(Clone, Debug, Default)]
+pub struct Requirements {
+    /// How many paths the type has.
+    count: usize,
+    /// The required paths, then the paths of required enums, which any
+    /// variant satisfies.
+    required: Vec<(usize, String)>,
+}
+
+impl Requirements {
+    pub fn new(paths: Vec<DescalePath>) -> Self {
+        let mut required = vec![];
+        let mut enums: Vec<(usize, String)> = vec![];
+        for (index, path) in paths.iter().enumerate().filter(|(_, p)| p.required) {
+            if !path.variant {
+                required.push((index, path.path.to_string()));
+                continue;
+            }
+            // Any variant of the enum will do, so look for the enum's own
+            // path. Its first variant is where it's marked.
+            let mount = match path.path.rsplit_once('.') {
+                Some((mount, _)) => mount,
+                None => "",
+            };
+            if !enums.iter().any(|(_, seen)| seen == mount) {
+                enums.push((index, mount.to_string()));
+            }
+        }
+        required.extend(enums);
+        Requirements {
+            count: paths.len(),
+            required,
+        }
+    }
+}
+
+/// The body of [`Descale::try_parse`], with the type's requirements worked
+/// out already.
+fn parse_with<'scale, T: Descale<'scale>>(
+    data: &'scale [u8],
+    type_id: u32,
+    types: &'scale PortableRegistry,
+    required: &Requirements,
+) -> Result<T, ParseError> {
+    let mut slf = T::empty();
+    let mut tracker = Tracker {
+        inner: &mut slf,
+        seen: vec![false; required.count],
+        error: None,
+    };
+    skeleton_decode(data, type_id, &mut tracker, types);
+    if let Some(err) = tracker.error {
+        return Err(err);
+    }
+    match required
+        .required
+        .iter()
+        .find(|(index, _)| !tracker.seen[*index])
+    {
+        Some((_, path)) => Err(ParseError {
+            path: path.clone(),
+            kind: ParseErrorKind::Missing,
+        }),
+        None => Ok(slf),
+    }
+}
+
+/// Passes everything on to `inner`, which notes the paths it sees, until a
+/// converter fails.
+struct Tracker<'a, T> {
+    inner: &'a mut T,
+    seen: Vec<bool>,
+    /// The converter error that stopped the walk.
+    error: Option<ParseError>,
+}
+
+impl<'scale, T: Descale<'scale>> VisitScale<'scale> for Tracker<'_, T> {
+    fn visit(
+        &mut self,
+        path: &[(PathSegment<'scale>, u32)],
+        data: &'scale [u8],
+        ty: &'scale Type<PortableForm>,
+        types: &'scale PortableRegistry,
+    ) {
+        let seen = Seen {
+            paths: &mut self.seen,
+            at: 0,
+        };
+        if let Err(err) = self.inner.try_visit(path, data, ty, types, seen) {
+            self.error = Some(err);
+        }
+    }
+
+    fn enter(
+        &mut self,
+        path: &[(PathSegment<'scale>, u32)],
+        ty_id: u32,
+        ty: &'scale Type<PortableForm>,
+    ) {
+        self.inner.enter(path, ty_id, ty);
+    }
+
+    fn leave(&mut self, path: &[(PathSegment<'scale>, u32)]) {
+        self.inner.leave(path);
+    }
+
+    fn variant(&mut self, path: &[(PathSegment<'scale>, u32)], data: &'scale [u8]) {
+        let seen = Seen {
+            paths: &mut self.seen,
+            at: 0,
+        };
+        self.inner.try_variant(path, data, seen);
+    }
+
+    fn stopped(&self) -> bool {
+        self.error.is_some()
+    }
+}
+
+/// Where the path has got to: a type, or the fields of a variant.
+enum Node<'a> {
+    Type(&'a Type<PortableForm>),
+    Fields(&'a [Field<PortableForm>]),
+}
+
+fn lookup(types: &PortableRegistry, id: u32) -> Result<&Type<PortableForm>, BindErrorKind> {
+    types.resolve(id).ok_or(BindErrorKind::UnknownType(id))
+}
+
+/// Whether the walker visits a value of this type rather than entering it.
+fn is_leaf(ty: &Type<PortableForm>, types: &PortableRegistry) -> bool {
+    let is_u8 = |id: u32| {
+        matches!(
+            types.resolve(id).map(|t| t.type_def()),
+            Some(TypeDef::Primitive(TypeDefPrimitive::U8))
+        )
+    };
+    match ty.type_def() {
+        TypeDef::Primitive(_) | TypeDef::Compact(_) | TypeDef::BitSequence(_) => true,
+        TypeDef::Sequence(seq) => is_u8(seq.type_param().id()),
+        TypeDef::Array(arr) => is_u8(arr.type_param().id()),
+        _ => false,
+    }
+}
+
+/// Follow a dotted path from `type_id`, naming segments the same way as the
+/// walker: field names (or positions for unnamed fields), variant names and
+/// element indexes. A `*` segment follows every one of them, so there can be
+/// several nodes at the end.
+fn resolve<'a>(
+    path: &str,
+    type_id: u32,
+    types: &'a PortableRegistry,
+) -> Result<Vec<Node<'a>>, BindErrorKind> {
+    let mut nodes = vec![Node::Type(lookup(types, type_id)?)];
+    for segment in path.split('.') {
+        let mut next = vec![];
+        for node in nodes {
+            children(node, segment, types, &mut next)?;
+        }
+        if next.is_empty() {
+            return Err(BindErrorKind::NotFound {
+                segment: segment.to_string(),
+            });
+        }
+        nodes = next;
+    }
+    Ok(nodes)
+}
+
+/// The position named by `segment`, if it's written the way the walker
+/// writes positions.
+fn index(segment: &str) -> Option<usize> {
+    segment
+        .parse()
+        .ok()
+        .filter(|i: &usize| i.to_string() == segment)
+}
+
+/// Push the children of `node` that `segment` matches onto `out`.
+fn children<'a>(
+    node: Node<'a>,
+    segment: &str,
+    types: &'a PortableRegistry,
+    out: &mut Vec<Node<'a>>,
+) -> Result<(), BindErrorKind> {
+    let wild = segment == "*";
+    let index = index(segment);
+    let ty = match node {
+        Node::Fields(fields) => return push_fields(fields, segment, types, out),
+        Node::Type(ty) if is_leaf(ty, types) => return Ok(()),
+        Node::Type(ty) => ty,
+    };
+    match ty.type_def() {
+        TypeDef::Composite(composite) => push_fields(composite.fields(), segment, types, out)?,
+        TypeDef::Variant(var) => out.extend(
+            var.variants()
+                .iter()
+                .filter(|v| wild || v.name().make_str() == segment)
+                .map(|v| Node::Fields(v.fields())),
+        ),
+        TypeDef::Sequence(seq) if wild || index.is_some() => {
+            out.push(Node::Type(lookup(types, seq.type_param().id())?))
+        }
+        TypeDef::Array(arr) if wild || index.is_some_and(|i| i < arr.len() as usize) => {
+            out.push(Node::Type(lookup(types, arr.type_param().id())?))
+        }
+        TypeDef::Tuple(tuple) => {
+            for (i, field) in tuple.fields().iter().enumerate() {
+                if wild || index == Some(i) {
+                    out.push(Node::Type(lookup(types, field.id())?));
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn push_fields<'a>(
+    fields: &'a [Field<PortableForm>],
+    segment: &str,
+    types: &'a PortableRegistry,
+    out: &mut Vec<Node<'a>>,
+) -> Result<(), BindErrorKind> {
+    for (i, field) in fields.iter().enumerate() {
+        let found = match field.name() {
+            Some(name) => name.make_str() == segment,
+            None => index(segment) == Some(i),
+        };
+        if found || segment == "*" {
+            out.push(Node::Type(lookup(types, field.ty().id())?));
+        }
+    }
+    Ok(())
+}
+
+/// Short description of a registry type for error messages.
+fn describe(ty: &Type<PortableForm>, types: &PortableRegistry) -> String {
+    if !ty.path().segments().is_empty() {
+        return ty.path().segments().join("::");
+    }
+    match ty.type_def() {
+        TypeDef::Primitive(prim) => format!("{:?}", prim).to_lowercase(),
+        TypeDef::Compact(inner) => match types.resolve(inner.type_param().id()) {
+            Some(inner) => format!("Compact<{}>", describe(inner, types)),
+            None => "Compact".into(),
+        },
+        TypeDef::Sequence(_) => "sequence".into(),
+        TypeDef::Array(arr) => format!("array of {}", arr.len()),
+        TypeDef::Tuple(_) => "tuple".into(),
+        TypeDef::BitSequence(_) => "bit sequence".into(),
+        TypeDef::Composite(_) => "struct".into(),
+        TypeDef::Variant(_) => "enum".into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::descale;
+    use crate::tests::make_type_id;
+    use parity_scale_codec::{Compact, Encode};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Encode, scale_info::TypeInfo)]
+    enum Call {
+        #[allow(dead_code)]
+        Remark(Vec<u8>),
+        Transfer {
+            dest: [u8; 4],
+            #[codec(compact)]
+            value: u128,
+            memo: (bool, String),
+        },
+    }
+
+    fn call() -> (Vec<u8>, u32, PortableRegistry) {
+        let encoded = Call::Transfer {
+            dest: [1, 2, 3, 4],
+            value: 500,
+            memo: (true, "rent".into()),
+        }
+        .encode();
+        let (id, types) = make_type_id::<Call>();
+        (encoded, id, types)
+    }
+
+    descale! {
+        struct Transfer<'scale> {
+            #[path("Transfer.dest")]
+            dest: &'scale [u8],
+            #[path("Transfer.memo.0")]
+            flag: bool,
+            #[path("Transfer.memo.1")]
+            memo: &'scale str,
+        }
+    }
+
+    /// Bind an arbitrary list of paths.
+    fn bind(paths: Vec<DescalePath>, types: &PortableRegistry, id: u32) -> Result<(), BindError> {
+        paths.into_iter().try_for_each(|p| p.check(id, types))
+    }
+
+    #[test]
+    fn bind_and_parse() {
+        let (encoded, id, types) = call();
+        let transfers = Transfer::bind(id, &types).unwrap();
+        assert_eq!(transfers.type_id(), id);
+        let transfer = transfers.parse(&encoded);
+        assert_eq!(Transfer::parse(&encoded, id, &types).memo, "rent");
+        assert_eq!(transfer.dest, &[1, 2, 3, 4]);
+        assert!(transfer.flag);
+        assert_eq!(transfer.memo, "rent");
+    }
+
+    #[derive(Debug, Default, PartialEq)]
+    enum Purpose {
+        #[default]
+        Unknown,
+        Rent,
+    }
+
+    fn purpose(memo: &str) -> Result<Purpose, String> {
+        match memo {
+            "rent" => Ok(Purpose::Rent),
+            other => Err(format!("unknown purpose {:?}", other)),
+        }
+    }
+
+    fn hex(raw: &[u8]) -> String {
+        raw.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    descale! {
+        struct Converted<'scale> {
+            #[path("Transfer.dest", with = hex)]
+            dest: String,
+            #[path("Transfer.value", with = |v: Compact<u128>| v.0 as f64 / 100.0)]
+            value: f64,
+            #[path("Transfer.memo.1", try_with = purpose)]
+            purpose: Purpose,
+        }
+    }
+
+    #[test]
+    fn converters() {
+        let (encoded, id, types) = call();
+        let bound = Converted::bind(id, &types).unwrap();
+        let converted = bound.try_parse(&encoded).unwrap();
+        assert_eq!(converted.dest, "01020304");
+        assert_eq!(converted.value, 5.0);
+        assert_eq!(converted.purpose, Purpose::Rent);
+
+        let food = Call::Transfer {
+            dest: [0; 4],
+            value: 1,
+            memo: (false, "food".into()),
+        }
+        .encode();
+        let err = bound.try_parse(&food).err().unwrap();
+        assert_eq!(err.path, "Transfer.memo.1");
+        assert!(matches!(err.kind, ParseErrorKind::Convert(_)));
+        assert_eq!(
+            err.to_string(),
+            "at Transfer.memo.1: conversion failed: unknown purpose \"food\""
+        );
+        let source = std::error::Error::source(&err).unwrap();
+        assert_eq!(source.to_string(), "unknown purpose \"food\"");
+        // The error doesn't outlive the parse it came from.
+        assert!(bound.try_parse(&encoded).is_ok());
+
+        // Nothing after a failed converter is visited.
+        static MEMOS: AtomicUsize = AtomicUsize::new(0);
+        descale! {
+            struct Early<'scale> {
+                #[path("Transfer.dest", try_with = |_: &[u8]| Err::<u8, _>("no dest"))]
+                dest: u8,
+                #[path("Transfer.memo.1", with = |_: &str| MEMOS.fetch_add(1, Ordering::Relaxed))]
+                memo: usize,
+            }
+        }
+        let err = Early::try_parse(&encoded, id, &types).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "at Transfer.dest: conversion failed: no dest"
+        );
+        assert_eq!(MEMOS.load(Ordering::Relaxed), 0);
+
+        // Bound by what the converter takes, not the field.
+        let err = bind(
+            vec![DescalePath::converted("Transfer.dest", |s: &str| s.len())],
+            &types,
+            id,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "at Transfer.dest: can't decode &str from array of 4"
+        );
+        let err = bind(
+            vec![DescalePath::converted("Transfer.memo.*", hex)],
+            &types,
+            id,
+        )
+        .unwrap_err();
+        assert!(matches!(err.kind, BindErrorKind::NotACollection { .. }));
+    }
+
+    #[test]
+    fn bad_paths() {
+        let (_, id, types) = call();
+        let err = |paths| bind(paths, &types, id).unwrap_err();
+
+        let typo = err(vec![
+            DescalePath::new::<bool>("Transfer.memo.0"),
+            DescalePath::new::<u8>("Transfr.value"),
+        ]);
+        assert_eq!(typo.path, "Transfr.value");
+        assert_eq!(
+            typo.kind,
+            BindErrorKind::NotFound {
+                segment: "Transfr".into()
+            }
+        );
+        assert_eq!(typo.to_string(), "at Transfr.value: no Transfr in the type");
+
+        for path in [
+            "Transfer.memo.2",
+            "Transfer.dest.4",
+            "Transfer.value.0",
+            "Remark.x",
+        ] {
+            assert!(
+                matches!(
+                    err(vec![DescalePath::new::<u8>(path)]).kind,
+                    BindErrorKind::NotFound { .. }
+                ),
+                "{}",
+                path
+            );
+        }
+
+        for path in ["Transfer", "Transfer.memo"] {
+            assert_eq!(
+                err(vec![DescalePath::new::<u8>(path)]).kind,
+                BindErrorKind::NotALeaf
+            );
+        }
+
+        let mismatch = err(vec![DescalePath::new::<bool>("Transfer.memo.1")]);
+        assert_eq!(
+            mismatch.to_string(),
+            "at Transfer.memo.1: can't decode bool from str"
+        );
+        // A fixed width int can't be decoded from a compact one or vice versa.
+        let mismatch = err(vec![DescalePath::new::<u128>("Transfer.value")]);
+        assert_eq!(
+            mismatch.kind,
+            BindErrorKind::TypeMismatch {
+                field_type: "u128",
+                found: "Compact<u128>".into()
+            }
+        );
+        assert!(bind(
+            vec![DescalePath::new::<Compact<u64>>("Transfer.value")],
+            &types,
+            id
+        )
+        .is_err());
+        assert!(bind(
+            vec![DescalePath::new::<Compact<u128>>("Transfer.value")],
+            &types,
+            id
+        )
+        .is_ok());
+
+        assert!(bind(vec![DescalePath::new::<&[u8]>("Remark.0")], &types, id).is_ok());
+        assert_eq!(
+            bind(vec![DescalePath::new::<u8>("x")], &types, 99)
+                .unwrap_err()
+                .kind,
+            BindErrorKind::UnknownType(99)
+        );
+    }
+
+    #[derive(Encode, scale_info::TypeInfo)]
+    struct Account {
+        name: String,
+        free: u32,
+    }
+
+    #[derive(Encode, scale_info::TypeInfo)]
+    struct Accounts {
+        accounts: Vec<Account>,
+        tags: (u8, u8, u16),
+    }
+
+    descale! {
+        struct Names<'scale> {
+            #[path("accounts.*.name")]
+            names: Vec<&'scale str>,
+            #[path("accounts.*.free")]
+            free: VecDeque<u32>,
+            #[path("accounts.*.name")]
+            lazy: DescaleIter<'scale, &'scale str>,
+            #[path("accounts.11.free")]
+            last: u32,
+        }
+    }
+
+    #[test]
+    fn wildcards() {
+        // More than the walker used to name.
+        let accounts = (0..12)
+            .map(|i| Account {
+                name: format!("acc{}", i),
+                free: i * 10,
+            })
+            .collect();
+        let encoded = Accounts {
+            accounts,
+            tags: (1, 2, 3),
+        }
+        .encode();
+        let (id, types) = make_type_id::<Accounts>();
+
+        let names = Names::bind(id, &types).unwrap().parse(&encoded);
+        assert_eq!(Names::parse(&encoded, id, &types).names, names.names);
+        assert_eq!(names.names.len(), 12);
+        assert_eq!(names.names[0], "acc0");
+        assert_eq!(names.names[11], "acc11");
+        assert_eq!(names.free.iter().sum::<u32>(), 660);
+        let mut lazy = names.lazy.clone();
+        assert_eq!(lazy.len(), 12);
+        assert_eq!(lazy.nth(11), Some("acc11"));
+        assert_eq!(lazy.next(), None);
+        assert!(names.lazy.eq(names.names.iter().copied()));
+        assert_eq!(names.last, 110);
+        let val = crate::ValueBuilder::parse(&encoded, id, &types);
+        assert_eq!(val.get("accounts.11.free"), Some(&crate::Value::U32(110)));
+
+        let err = |path| bind(vec![path], &types, id).unwrap_err().kind;
+        assert_eq!(
+            err(DescalePath::new::<u32>("accounts.*.free")),
+            BindErrorKind::NotACollection { field_type: "u32" }
+        );
+        assert!(matches!(
+            err(DescalePath::new::<Vec<u8>>("tags.*")),
+            BindErrorKind::TypeMismatch { found, .. } if found == "u16"
+        ));
+        assert_eq!(
+            err(DescalePath::new::<Vec<u8>>("*.*.nam")),
+            BindErrorKind::NotFound {
+                segment: "nam".into()
+            }
+        );
+        assert!(bind(vec![DescalePath::new::<Vec<u16>>("tags.2")], &types, id).is_ok());
+
+        let path = [
+            (PathSegment::name("accounts"), 0),
+            (PathSegment::index(3), 0),
+            (PathSegment::name("name"), 0),
+        ];
+        assert!(path_matches(&path, "accounts.*.name"));
+        assert!(path_matches(&path, "*.*.*"));
+        assert!(!path_matches(&path, "accounts.*"));
+        assert!(!path_matches(&path, "accounts.*.name.*"));
+        assert!(!path_matches(&path, "accounts.4.name"));
+
+        #[cfg(feature = "smallvec")]
+        {
+            let mut small = SmallVec::<[u16; 2]>::new();
+            for data in [[1, 0], [2, 0], [3, 0]] {
+                small.decode_into(&data);
+            }
+            assert_eq!(small.as_slice(), &[1, 2, 3]);
+        }
+    }
+
+    #[derive(Encode, scale_info::TypeInfo)]
+    struct Signature {
+        signer: [u8; 4],
+        sig: (u8, Vec<u8>),
+    }
+
+    #[derive(Encode, scale_info::TypeInfo)]
+    struct Extrinsic {
+        signature: Option<Signature>,
+        call: Call,
+    }
+
+    descale! {
+        struct SigParts<'scale> {
+            #[path("signer")]
+            signer: &'scale [u8],
+            #[path("sig.1")]
+            bytes: &'scale [u8],
+        }
+    }
+
+    descale! {
+        struct Signed<'scale> {
+            #[path("signature.Some.0")]
+            #[required]
+            sig: SigParts<'scale>,
+            #[path("call.Transfer.memo.1")]
+            memo: &'scale str,
+        }
+    }
+
+    #[test]
+    fn nested() {
+        let signed = |signature| {
+            Extrinsic {
+                signature,
+                call: Call::Transfer {
+                    dest: [0; 4],
+                    value: 1,
+                    memo: (false, "hi".into()),
+                },
+            }
+            .encode()
+        };
+        let (id, types) = make_type_id::<Extrinsic>();
+
+        let paths: Vec<_> = Signed::paths().into_iter().map(|p| p.path).collect();
+        assert_eq!(
+            paths,
+            [
+                "signature.Some.0.signer",
+                "signature.Some.0.sig.1",
+                "call.Transfer.memo.1"
+            ]
+        );
+        assert_eq!(<Signed as DescaleField>::PATHS, paths.len());
+
+        let encoded = signed(Some(Signature {
+            signer: [7; 4],
+            sig: (1, vec![2, 3]),
+        }));
+        let bound = Signed::bind(id, &types).unwrap();
+        let signed_ext = bound.parse(&encoded);
+        assert_eq!(signed_ext.sig.signer, &[7; 4]);
+        assert_eq!(signed_ext.sig.bytes, &[2, 3]);
+        assert_eq!(signed_ext.memo, "hi");
+        assert_eq!(Signed::parse(&encoded, id, &types).sig.bytes, &[2, 3]);
+
+        // The mount is required, so everything in it is.
+        let err = bound.try_parse(&signed(None)).err().unwrap();
+        assert_eq!(err.path, "signature.Some.0.signer");
+
+        // Mounted somewhere without those paths.
+        descale! {
+            struct Misplaced<'scale> {
+                #[path("call.Transfer")]
+                sig: SigParts<'scale>,
+            }
+        }
+        let err = Misplaced::bind(id, &types).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "at call.Transfer.signer: no signer in the type"
+        );
+        assert!(Misplaced::parse(&encoded, id, &types).sig.signer.is_empty());
+    }
+
+    #[derive(Encode, scale_info::TypeInfo)]
+    enum Op {
+        Stop,
+        Move {
+            x: u8,
+            y: Option<u16>,
+        },
+        #[allow(dead_code)]
+        Say(String),
+        Jump(u32, Vec<u8>),
+    }
+
+    #[derive(Encode, scale_info::TypeInfo)]
+    struct Step {
+        op: Op,
+        label: String,
+    }
+
+    crate::descale_enum! {
+        enum OpParts<'scale> {
+            #[variant("Move")]
+            Move {
+                #[path("x")]
+                x: u8,
+                #[path("y.Some.0")]
+                y: Option<u16>,
+                #[path("x", with = |x: u8| x * 2)]
+                doubled: u8,
+            },
+            #[variant("Stop")]
+            Stop {},
+            #[other]
+            Other { name: &'scale str, data: &'scale [u8] },
+        }
+    }
+
+    descale! {
+        struct StepParts<'scale> {
+            #[path("op")]
+            op: OpParts<'scale>,
+            #[path("label")]
+            label: &'scale str,
+        }
+    }
+
+    #[test]
+    fn enums() {
+        let (id, types) = make_type_id::<Step>();
+        let step = |op| {
+            Step {
+                op,
+                label: "l".into(),
+            }
+            .encode()
+        };
+
+        let bound = StepParts::bind(id, &types).unwrap();
+        let moved = step(Op::Move { x: 1, y: Some(2) });
+        assert!(matches!(
+            bound.parse(&moved).op,
+            OpParts::Move {
+                x: 1,
+                y: Some(2),
+                doubled: 2
+            }
+        ));
+        let moved = step(Op::Move { x: 3, y: None });
+        assert!(matches!(
+            bound.parse(&moved).op,
+            OpParts::Move {
+                x: 3,
+                y: None,
+                doubled: 6
+            }
+        ));
+        assert!(matches!(bound.parse(&step(Op::Stop)).op, OpParts::Stop {}));
+
+        let jump = step(Op::Jump(7, vec![1, 2]));
+        let parts = bound.parse(&jump);
+        assert_eq!(parts.label, "l");
+        match parts.op {
+            OpParts::Other { name, data } => {
+                assert_eq!(name, "Jump");
+                assert_eq!(data, (7u32, vec![1u8, 2]).encode());
+            }
+            _ => panic!("expected the other arm"),
+        }
+
+        // On its own, from the enum's type.
+        let op_id = match types.resolve(id).unwrap().type_def() {
+            TypeDef::Composite(c) => c.fields()[0].ty().id(),
+            _ => unreachable!(),
+        };
+        let stop = Op::Stop.encode();
+        let op = OpParts::bind(op_id, &types).unwrap().parse(&stop);
+        assert!(matches!(op, OpParts::Stop {}));
+        assert!(matches!(
+            OpParts::default(),
+            OpParts::Other { name: "", data: [] }
+        ));
+
+        let err = |path| bind(vec![DescalePath::variant(path)], &types, id).unwrap_err();
+        assert_eq!(
+            err("op.Mvoe").kind,
+            BindErrorKind::NotFound {
+                segment: "Mvoe".into()
+            }
+        );
+        assert_eq!(err("label").to_string(), "at label: not an enum variant");
+    }
+
+    #[test]
+    fn required_enums() {
+        descale! {
+            struct NextOp<'scale> {
+                #[path("Some.0")]
+                #[required]
+                op: OpParts<'scale>,
+            }
+        }
+        let (id, types) = make_type_id::<Option<Op>>();
+        let bound = NextOp::bind(id, &types).unwrap();
+
+        // Whichever arm is there, even one without fields or the catch-all.
+        let stop = Some(Op::Stop).encode();
+        assert!(matches!(
+            bound.try_parse(&stop).unwrap().op,
+            OpParts::Stop {}
+        ));
+        let moved = Some(Op::Move { x: 1, y: None }).encode();
+        let op = bound.try_parse(&moved).unwrap().op;
+        assert!(matches!(op, OpParts::Move { x: 1, y: None, .. }));
+        let jump = Some(Op::Jump(7, vec![])).encode();
+        let op = bound.try_parse(&jump).unwrap().op;
+        assert!(matches!(op, OpParts::Other { name: "Jump", .. }));
+
+        let err = bound.try_parse(&None::<Op>.encode()).err().unwrap();
+        assert_eq!(err.path, "Some.0");
+        assert!(matches!(err.kind, ParseErrorKind::Missing));
+
+        // The same without binding, from the requirements cached on the
+        // first parse.
+        assert_eq!(<NextOp as DescaleField>::PATHS, NextOp::paths().len());
+        assert!(NextOp::try_parse(&stop, id, &types).is_ok());
+        let none = None::<Op>.encode();
+        let err = NextOp::try_parse(&none, id, &types).err().unwrap();
+        assert_eq!(err.path, "Some.0");
+    }
+}
@@ -0,0 +1,282 @@
+//! Structural comparison of two [`Value`] trees, e.g. a storage item before
+//! and after a runtime upgrade.
+//!
+//! Fields are matched by name, so sequence elements are matched by index and
+//! enums by variant (a changed variant is a single modification of the enum,
+//! and the same goes for options and results).
+//! `_ty` fields are ignored as type ids commonly shift between runtimes.
+use crate::walk::ValueVisitor;
+use crate::Value;
+#[cfg(feature = "display")]
+use crate::ValueFormatter;
+#[cfg(feature = "display")]
+use core::fmt::{Display, Formatter};
+
+/// One difference, keyed by the dotted path (as used by [`Value::get`]) of
+/// the value that changed. The root's path is the empty string.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change<'a, 'scale> {
+    Added {
+        path: String,
+        new: &'a Value<'scale>,
+    },
+    Removed {
+        path: String,
+        old: &'a Value<'scale>,
+    },
+    Modified {
+        path: String,
+        old: &'a Value<'scale>,
+        new: &'a Value<'scale>,
+    },
+}
+
+impl<'a, 'scale> Change<'a, 'scale> {
+    pub fn path(&self) -> &str {
+        match self {
+            Change::Added { path, .. } | Change::Removed { path, .. } => path,
+            Change::Modified { path, .. } => path,
+        }
+    }
+}
+
+/// The result of [`Value::diff`]. Displays as a report with one line per
+/// change: `+ path: new`, `- path: old` or `~ path: old -> new`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Diff<'a, 'scale> {
+    changes: Vec<Change<'a, 'scale>>,
+}
+
+impl<'a, 'scale> Diff<'a, 'scale> {
+    pub fn changes(&self) -> &[Change<'a, 'scale>] {
+        &self.changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl<'a, 'scale> IntoIterator for Diff<'a, 'scale> {
+    type Item = Change<'a, 'scale>;
+    type IntoIter = std::vec::IntoIter<Change<'a, 'scale>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.changes.into_iter()
+    }
+}
+
+#[cfg(feature = "display")]
+impl Display for Diff<'_, '_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let fmt = ValueFormatter::new().type_ids(false);
+        for change in &self.changes {
+            let path = match change.path() {
+                "" => ".",
+                path => path,
+            };
+            match change {
+                Change::Added { new, .. } => writeln!(f, "+ {}: {}", path, fmt.format(new))?,
+                Change::Removed { old, .. } => writeln!(f, "- {}: {}", path, fmt.format(old))?,
+                Change::Modified { old, new, .. } => {
+                    writeln!(f, "~ {}: {} -> {}", path, fmt.format(old), fmt.format(new))?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Walks the old tree, keeping the matching value of the new tree (if any)
+/// for each value on the current path.
+struct Differ<'a, 'scale> {
+    new: &'a Value<'scale>,
+    /// The new counterpart of each value on the path, and whether its
+    /// children are being compared field by field.
+    stack: Vec<(Option<&'a Value<'scale>>, bool)>,
+    changes: Vec<Change<'a, 'scale>>,
+}
+
+impl<'a, 'scale> Differ<'a, 'scale> {
+    /// Whether `old` and `new` should be compared child by child rather than
+    /// as a whole, recording the change if not.
+    fn descend(
+        &mut self,
+        path: &[&'a str],
+        old: &'a Value<'scale>,
+        new: &'a Value<'scale>,
+    ) -> bool {
+        let nested = match (old, new) {
+            (Value::Object(_), Value::Object(_)) => match (old.variant(), new.variant()) {
+                (Some((old_name, _)), Some((new_name, _))) => old_name == new_name,
+                (None, None) => true,
+                _ => false,
+            },
+            (Value::Some(_), Value::Some(_))
+            | (Value::Ok(_), Value::Ok(_))
+            | (Value::Err(_), Value::Err(_)) => true,
+            _ => false,
+        };
+        if !nested && old != new {
+            self.changes.push(Change::Modified {
+                path: path.join("."),
+                old,
+                new,
+            });
+        }
+        nested
+    }
+}
+
+impl<'a, 'scale> ValueVisitor<'a, 'scale> for Differ<'a, 'scale> {
+    fn pre(&mut self, path: &[&'a str], old: &'a Value<'scale>) -> bool {
+        let new = match (path.last(), self.stack.last()) {
+            (None, _) => Some(self.new),
+            (Some(name), Some((Some(parent), _))) => parent.find(name),
+            _ => None,
+        };
+        let descend = match new {
+            Some(new) => self.descend(path, old, new),
+            None => {
+                self.changes.push(Change::Removed {
+                    path: path.join("."),
+                    old,
+                });
+                false
+            }
+        };
+        self.stack.push((new, descend));
+        descend
+    }
+
+    fn post(&mut self, path: &[&'a str], old: &'a Value<'scale>) {
+        // Fields only in the new object come after the ones compared.
+        if let Some((Some(Value::Object(new_fields)), true)) = self.stack.pop() {
+            for (name, new_val) in new_fields.iter().filter(|(name, _)| name != "_ty") {
+                if old.find(name).is_none() {
+                    let mut path = path.to_vec();
+                    path.push(name);
+                    self.changes.push(Change::Added {
+                        path: path.join("."),
+                        new: new_val,
+                    });
+                }
+            }
+        }
+    }
+}
+
+impl<'scale> Value<'scale> {
+    /// The changes that turn `self` into `other`, in document order.
+    pub fn diff<'a>(&'a self, other: &'a Value<'scale>) -> Diff<'a, 'scale> {
+        let mut differ = Differ {
+            new: other,
+            stack: vec![],
+            changes: vec![],
+        };
+        self.walk(&mut differ);
+        Diff {
+            changes: differ.changes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::make_type_id;
+    use crate::ValueBuilder;
+    use parity_scale_codec::Encode;
+
+    #[derive(Encode, scale_info::TypeInfo)]
+    enum Status {
+        Active { since: u32 },
+        Frozen { reason: String },
+    }
+
+    #[derive(Encode, scale_info::TypeInfo)]
+    struct Account {
+        balance: u64,
+        status: Status,
+        history: Vec<u16>,
+    }
+
+    #[test]
+    fn changes() {
+        let before = Account {
+            balance: 10,
+            status: Status::Active { since: 1 },
+            history: vec![1, 2, 3],
+        }
+        .encode();
+        let after = Account {
+            balance: 12,
+            status: Status::Active { since: 5 },
+            history: vec![1, 7],
+        }
+        .encode();
+        let (id, types) = make_type_id::<Account>();
+        let before = ValueBuilder::parse(&before, id, &types);
+        let after = ValueBuilder::parse(&after, id, &types);
+
+        assert!(before.diff(&before).is_empty());
+
+        let diff = before.diff(&after);
+        let paths: Vec<_> = diff.changes().iter().map(|c| c.path()).collect();
+        assert_eq!(
+            paths,
+            vec!["balance", "status.Active.since", "history.1", "history.2"]
+        );
+        assert_eq!(
+            diff.changes()[3],
+            Change::Removed {
+                path: "history.2".into(),
+                old: &Value::U16(3)
+            }
+        );
+        #[cfg(feature = "display")]
+        assert_eq!(
+            diff.to_string(),
+            "~ balance: U64(10) -> U64(12)\n\
+             ~ status.Active.since: U32(1) -> U32(5)\n\
+             ~ history.1: U16(2) -> U16(7)\n\
+             - history.2: U16(3)\n"
+        );
+
+        let reversed = after.diff(&before);
+        assert_eq!(reversed.changes().len(), 4);
+        assert!(matches!(
+            &reversed.changes()[3],
+            Change::Added { path, new: Value::U16(3) } if path == "history.2"
+        ));
+    }
+
+    #[test]
+    fn variant_change_is_one_modification() {
+        let before = Account {
+            balance: 1,
+            status: Status::Active { since: 1 },
+            history: vec![],
+        }
+        .encode();
+        let after = Account {
+            balance: 1,
+            status: Status::Frozen {
+                reason: "upgrade".into(),
+            },
+            history: vec![],
+        }
+        .encode();
+        let (id, types) = make_type_id::<Account>();
+        let before = ValueBuilder::parse(&before, id, &types);
+        let after = ValueBuilder::parse(&after, id, &types);
+
+        let diff = before.diff(&after);
+        assert_eq!(diff.changes().len(), 1);
+        #[cfg(feature = "display")]
+        assert_eq!(
+            diff.to_string(),
+            "~ status: {Active: {since: U32(1)}} -> {Frozen: {reason: Str(\"upgrade\")}}\n"
+        );
+    }
+}
@@ -0,0 +1,391 @@
+//! In place editing of [`Value`] trees by dotted path, e.g. to redact fields
+//! before serializing or to adjust them before re-encoding.
+use crate::value::Fields;
+use crate::Value;
+use core::fmt::{Display, Formatter};
+use std::borrow::Cow;
+
+/// Why an edit couldn't be made.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EditError {
+    /// The path up to and including the segment that failed.
+    pub path: String,
+    pub kind: EditErrorKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EditErrorKind {
+    /// There's no field at `path`.
+    NotFound,
+    /// The value at `path` is a leaf so can't contain fields.
+    NotAnObject,
+    /// `path` doesn't end with an index into a sequence.
+    NotASequence,
+}
+
+impl Display for EditError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self.kind {
+            EditErrorKind::NotFound => write!(f, "no value at {}", self.path),
+            EditErrorKind::NotAnObject => write!(f, "{} is not an object", self.path),
+            EditErrorKind::NotASequence => write!(f, "{} is not a sequence index", self.path),
+        }
+    }
+}
+
+impl std::error::Error for EditError {}
+
+impl<'scale> Value<'scale> {
+    /// Mutable version of [`Value::get`].
+    pub fn get_mut(&mut self, path: &str) -> Option<&mut Value<'scale>> {
+        path.split('.').try_fold(self, |cur, pa| cur.find_mut(pa))
+    }
+
+    pub fn find_mut(&mut self, find1: &str) -> Option<&mut Self> {
+        let wrapper = self.wrapper();
+        match self {
+            Self::Object(fields) => {
+                for (field, val) in fields.iter_mut() {
+                    if *field == find1 {
+                        return Some(val);
+                    }
+                }
+            }
+            Self::Some(val) | Self::Ok(val) | Self::Err(val) if wrapper == Some(find1) => {
+                return Some(val)
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// The fields of the object that the last segment of `path` lives in.
+    fn parent_fields<'p>(
+        &mut self,
+        path: &'p str,
+    ) -> Result<(&mut Fields<'scale>, &'p str), EditError> {
+        let (parent, last) = match path.rsplit_once('.') {
+            Some((parent, last)) => (Some(parent), last),
+            None => (None, path),
+        };
+        let mut cur = self;
+        let mut walked = 0;
+        for segment in parent.into_iter().flat_map(|p| p.split('.')) {
+            walked += segment.len() + 1;
+            let err = |kind| EditError {
+                path: path[..walked - 1].into(),
+                kind,
+            };
+            // Step through `Some`, `Ok` and `Err` like `get_mut` does.
+            if !matches!(
+                cur,
+                Value::Object(_) | Value::Some(_) | Value::Ok(_) | Value::Err(_)
+            ) {
+                return Err(err(EditErrorKind::NotAnObject));
+            }
+            cur = cur.find_mut(segment).ok_or(err(EditErrorKind::NotFound))?;
+        }
+        match cur {
+            Value::Object(fields) => Ok((fields, last)),
+            _ => Err(EditError {
+                path: path[..walked.saturating_sub(1)].into(),
+                kind: EditErrorKind::NotAnObject,
+            }),
+        }
+    }
+
+    /// Replace the value at `path`, returning the old one, or add it as a new
+    /// last field of its parent if there isn't one.
+    ///
+    /// Fails if the parent doesn't exist or isn't an object.
+    pub fn set(
+        &mut self,
+        path: &str,
+        new: Value<'scale>,
+    ) -> Result<Option<Value<'scale>>, EditError> {
+        let (fields, last) = self.parent_fields(path)?;
+        match fields.iter_mut().find(|(name, _)| name == last) {
+            Some((_, val)) => Ok(Some(std::mem::replace(val, new))),
+            None => {
+                fields.push((Cow::Owned(last.into()), new));
+                Ok(None)
+            }
+        }
+    }
+
+    /// Remove and return the value at `path`.
+    ///
+    /// Removing an element of a sequence (an object whose fields are `0`, `1`,
+    /// ...) renumbers the ones after it.
+    pub fn remove(&mut self, path: &str) -> Result<Value<'scale>, EditError> {
+        let (fields, last) = self.parent_fields(path)?;
+        let seq = is_seq(fields);
+        match fields.iter().position(|(name, _)| name == last) {
+            Some(i) => {
+                let removed = fields.remove(i).1;
+                if seq {
+                    renumber(&mut fields[i..], last.parse().unwrap());
+                }
+                Ok(removed)
+            }
+            None => Err(EditError {
+                path: path.into(),
+                kind: EditErrorKind::NotFound,
+            }),
+        }
+    }
+
+    /// Insert `new` into a sequence at the index `path` ends with, moving the
+    /// elements from there on up by one. The index can be the length of the
+    /// sequence, to append.
+    ///
+    /// Fails if the parent doesn't exist or isn't a sequence, or the index is
+    /// past the end.
+    pub fn insert(&mut self, path: &str, new: Value<'scale>) -> Result<(), EditError> {
+        let (fields, last) = self.parent_fields(path)?;
+        let err = |kind| EditError {
+            path: path.into(),
+            kind,
+        };
+        let index: usize = match last.parse() {
+            Ok(index) if is_seq(fields) => index,
+            _ => return Err(err(EditErrorKind::NotASequence)),
+        };
+        let first = fields.iter().take_while(|(name, _)| name == "_ty").count();
+        if first + index > fields.len() {
+            return Err(err(EditErrorKind::NotFound));
+        }
+        fields.insert(first + index, (Cow::Owned(last.into()), new));
+        renumber(&mut fields[first + index + 1..], index + 1);
+        Ok(())
+    }
+
+    /// Call `f` with the path and value of every leaf (anything that isn't an
+    /// object, `Some`, `Ok` or `Err`), depth first. `_ty` fields are skipped.
+    pub fn map_leaves<F: FnMut(&str, &mut Value<'scale>)>(&mut self, mut f: F) {
+        fn walk<'scale, F: FnMut(&str, &mut Value<'scale>)>(
+            val: &mut Value<'scale>,
+            path: &mut String,
+            f: &mut F,
+        ) {
+            let wrapper = val.wrapper();
+            match val {
+                Value::Object(fields) => {
+                    for (name, child) in fields.iter_mut().filter(|(name, _)| name != "_ty") {
+                        let len = path.len();
+                        if len > 0 {
+                            path.push('.');
+                        }
+                        path.push_str(name);
+                        walk(child, path, f);
+                        path.truncate(len);
+                    }
+                }
+                Value::Some(inner) | Value::Ok(inner) | Value::Err(inner) => {
+                    let len = path.len();
+                    if len > 0 {
+                        path.push('.');
+                    }
+                    path.push_str(wrapper.unwrap());
+                    walk(inner, path, f);
+                    path.truncate(len);
+                }
+                leaf => f(path, leaf),
+            }
+        }
+        walk(self, &mut String::new(), &mut f);
+    }
+}
+
+/// Whether the fields (other than `_ty`) are `0`, `1`, ... An empty object
+/// counts, so the first element can be inserted.
+fn is_seq(fields: &Fields) -> bool {
+    fields
+        .iter()
+        .filter(|(name, _)| name != "_ty")
+        .enumerate()
+        .all(|(i, (name, _))| *name == i.to_string())
+}
+
+/// Name sequence elements `from`, `from + 1`, ...
+fn renumber(elems: &mut [(Cow<'_, str>, Value<'_>)], from: usize) {
+    for (i, (name, _)) in elems.iter_mut().enumerate() {
+        *name = Cow::Owned((from + i).to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode::encode_value;
+    use crate::tests::make_type_id;
+    use crate::ValueBuilder;
+    use parity_scale_codec::Encode;
+
+    #[derive(Encode, scale_info::TypeInfo)]
+    struct Login {
+        user: String,
+        password: String,
+        attempts: Vec<u32>,
+    }
+
+    #[test]
+    fn set_remove_get_mut() {
+        let (id, types) = make_type_id::<Login>();
+        let encoded = Login {
+            user: "bob".into(),
+            password: "hunter2".into(),
+            attempts: vec![1, 2],
+        }
+        .encode();
+        let mut val = ValueBuilder::parse(&encoded, id, &types);
+
+        assert_eq!(
+            val.set("password", Value::Str("***".into())).unwrap(),
+            Some(Value::Str("hunter2".into()))
+        );
+        *val.get_mut("attempts.1").unwrap() = Value::U32(9);
+        assert_eq!(val.remove("attempts.0").unwrap(), Value::U32(1));
+        assert_eq!(val.get("attempts.0"), Some(&Value::U32(9)));
+        val.insert("attempts.1", Value::U32(5)).unwrap();
+        val.insert("attempts.0", Value::U32(3)).unwrap();
+        assert_eq!(val.get("attempts.1"), Some(&Value::U32(9)));
+
+        let expected = Login {
+            user: "bob".into(),
+            password: "***".into(),
+            attempts: vec![3, 9, 5],
+        };
+        let mut out = vec![];
+        encode_value(&val, id, &types, &mut out).unwrap();
+        assert_eq!(out, expected.encode());
+    }
+
+    #[test]
+    fn errors() {
+        let (id, types) = make_type_id::<Login>();
+        let encoded = Login {
+            user: "bob".into(),
+            password: "pw".into(),
+            attempts: vec![1],
+        }
+        .encode();
+        let mut val = ValueBuilder::parse(&encoded, id, &types);
+
+        let err = val.set("missing.x", Value::Bool(true)).unwrap_err();
+        assert_eq!(err.path, "missing");
+        assert_eq!(err.kind, EditErrorKind::NotFound);
+
+        let err = val.remove("user.x").unwrap_err();
+        assert_eq!(err.to_string(), "user is not an object");
+
+        let err = val.remove("attempts.3").unwrap_err();
+        assert_eq!(err.to_string(), "no value at attempts.3");
+        assert!(val.get_mut("attempts.3").is_none());
+
+        let err = val.insert("attempts.2", Value::U32(0)).unwrap_err();
+        assert_eq!(err.kind, EditErrorKind::NotFound);
+        let err = val.insert("user", Value::U32(0)).unwrap_err();
+        assert_eq!(err.to_string(), "user is not a sequence index");
+    }
+
+    #[derive(Encode, scale_info::TypeInfo)]
+    struct Session {
+        owner: Option<Login>,
+    }
+
+    #[test]
+    fn through_options() {
+        let (id, types) = make_type_id::<Session>();
+        let encoded = Session {
+            owner: Some(Login {
+                user: "bob".into(),
+                password: "pw".into(),
+                attempts: vec![1, 2],
+            }),
+        }
+        .encode();
+        let mut val = ValueBuilder::parse(&encoded, id, &types);
+
+        val.set("owner.Some.password", Value::Str("***".into()))
+            .unwrap();
+        assert_eq!(val.remove("owner.Some.attempts.0").unwrap(), Value::U32(1));
+        val.insert("owner.Some.attempts.1", Value::U32(3)).unwrap();
+        assert_eq!(
+            val.get("owner.Some.password"),
+            Some(&Value::Str("***".into()))
+        );
+
+        let err = val.set("owner.Some.user.x", Value::U32(0)).unwrap_err();
+        assert_eq!(err.to_string(), "owner.Some.user is not an object");
+        let err = val.set("owner.None.x", Value::U32(0)).unwrap_err();
+        assert_eq!(err.to_string(), "no value at owner.None");
+
+        let expected = Session {
+            owner: Some(Login {
+                user: "bob".into(),
+                password: "***".into(),
+                attempts: vec![2, 3],
+            }),
+        };
+        let mut out = vec![];
+        encode_value(&val, id, &types, &mut out).unwrap();
+        assert_eq!(out, expected.encode());
+    }
+
+    #[test]
+    fn map_leaves() {
+        let (id, types) = make_type_id::<Login>();
+        let encoded = Login {
+            user: "bob".into(),
+            password: "pw".into(),
+            attempts: vec![1, 2],
+        }
+        .encode();
+        let mut val = ValueBuilder::parse(&encoded, id, &types);
+
+        let mut paths = vec![];
+        val.map_leaves(|path, leaf| {
+            paths.push(path.to_string());
+            if let Value::U32(n) = leaf {
+                *n *= 10;
+            }
+            if path == "password" {
+                *leaf = Value::Str("".into());
+            }
+        });
+        assert_eq!(paths, vec!["user", "password", "attempts.0", "attempts.1"]);
+        assert_eq!(val.get("attempts.1"), Some(&Value::U32(20)));
+        assert_eq!(val.get("password"), Some(&Value::Str("".into())));
+        assert_eq!(val.type_id(), Some(id));
+
+        // Each of several fields with the same name is visited once.
+        let mut val = Value::Object(Box::new(vec![
+            ("a".into(), Value::U32(1)),
+            ("a".into(), Value::U32(2)),
+            (
+                "b".into(),
+                Value::Object(Box::new(vec![("c".into(), Value::U32(3))])),
+            ),
+            ("b".into(), Value::U32(4)),
+        ]));
+        let mut paths = vec![];
+        val.map_leaves(|path, leaf| {
+            paths.push(path.to_string());
+            if let Value::U32(n) = leaf {
+                *n += 10;
+            }
+        });
+        assert_eq!(paths, vec!["a", "a", "b.c", "b"]);
+        let leaves: Vec<_> = val.leaves().map(|(_, leaf)| leaf.clone()).collect();
+        assert_eq!(
+            leaves,
+            vec![
+                Value::U32(11),
+                Value::U32(12),
+                Value::U32(13),
+                Value::U32(14)
+            ]
+        );
+    }
+}
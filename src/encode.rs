@@ -0,0 +1,496 @@
+//! Encode a [`Value`] back to SCALE by walking the registry type, the reverse
+//! of [`ValueBuilder::parse`](crate::ValueBuilder::parse).
+//!
+//! Any integer variant is accepted for any integer type as long as it fits, so
+//! values that went through `scale_value` (which widens to 128 bits) encode fine.
+use crate::{ToStr, Value};
+use core::fmt::{Display, Formatter};
+use parity_scale_codec::{Compact, Encode};
+use scale_info::form::PortableForm;
+use scale_info::{Field, PortableRegistry, TypeDef, TypeDefPrimitive};
+use std::borrow::Cow;
+
+/// Why a value couldn't be encoded, and where in the value it happened.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EncodeError {
+    /// Dotted path to the offending value, e.g. `outer.0.val`.
+    pub path: String,
+    pub kind: EncodeErrorKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EncodeErrorKind {
+    /// The registry doesn't contain this type id.
+    UnknownType(u32),
+    /// The value's shape doesn't match the type, e.g. a `Str` for a `bool`.
+    TypeMismatch {
+        expected: String,
+        found: String,
+    },
+    /// A struct field (or tuple element) is missing from the object.
+    MissingField(String),
+    /// The enum type has no variant of this name.
+    UnknownVariant(String),
+    /// The number doesn't fit in the target type.
+    OutOfRange(String),
+    /// A fixed size array of the wrong length.
+    WrongLength {
+        expected: usize,
+        found: usize,
+    },
+    Unsupported(String),
+}
+
+impl Display for EncodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        if !self.path.is_empty() {
+            write!(f, "at {}: ", self.path)?;
+        }
+        match &self.kind {
+            EncodeErrorKind::UnknownType(id) => write!(f, "type {} not found in registry", id),
+            EncodeErrorKind::TypeMismatch { expected, found } => {
+                write!(f, "expected {}, found {}", expected, found)
+            }
+            EncodeErrorKind::MissingField(name) => write!(f, "missing field {}", name),
+            EncodeErrorKind::UnknownVariant(name) => write!(f, "unknown variant {}", name),
+            EncodeErrorKind::OutOfRange(ty) => write!(f, "number out of range for {}", ty),
+            EncodeErrorKind::WrongLength { expected, found } => {
+                write!(f, "expected {} elements, found {}", expected, found)
+            }
+            EncodeErrorKind::Unsupported(what) => write!(f, "unsupported: {}", what),
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+/// Append the SCALE encoding of `value`, as registry type `type_id`, to `out`.
+///
+/// On error `out` may contain a partial encoding.
+pub fn encode_value(
+    value: &Value,
+    type_id: u32,
+    types: &PortableRegistry,
+    out: &mut Vec<u8>,
+) -> Result<(), EncodeError> {
+    Encoder {
+        types,
+        path: vec![],
+        out,
+    }
+    .encode(value, type_id)
+}
+
+struct Encoder<'a> {
+    types: &'a PortableRegistry,
+    path: Vec<Cow<'a, str>>,
+    out: &'a mut Vec<u8>,
+}
+
+/// Short description of a value for error messages.
+fn describe(value: &Value) -> String {
+    match value {
+        Value::Object(_) if value.variant().is_some() => "enum".into(),
+        Value::Object(_) => "object".into(),
+        other => format!("{:?}", other)
+            .split('(')
+            .next()
+            .unwrap_or_default()
+            .to_string(),
+    }
+}
+
+impl<'a> Encoder<'a> {
+    fn err<T>(&self, kind: EncodeErrorKind) -> Result<T, EncodeError> {
+        Err(EncodeError {
+            path: self.path.join("."),
+            kind,
+        })
+    }
+
+    fn mismatch<T>(&self, expected: &str, found: &Value) -> Result<T, EncodeError> {
+        self.err(EncodeErrorKind::TypeMismatch {
+            expected: expected.into(),
+            found: describe(found),
+        })
+    }
+
+    fn encode(&mut self, value: &Value, type_id: u32) -> Result<(), EncodeError> {
+        let types: &'a PortableRegistry = self.types;
+        let ty = match types.resolve(type_id) {
+            Some(ty) => ty,
+            None => return self.err(EncodeErrorKind::UnknownType(type_id)),
+        };
+        match ty.type_def() {
+            TypeDef::Composite(composite) => self.encode_fields(value, composite.fields()),
+            TypeDef::Variant(var) => {
+                // `inner` is the variant's fields object, or for the native
+                // option and result forms the single field itself.
+                let (name, inner) = match value {
+                    Value::Object(fields) => {
+                        let mut fields = fields.iter().filter(|(name, _)| name != "_ty");
+                        match (fields.next(), fields.next()) {
+                            (Some((name, inner)), None) => (name.as_ref(), Some(inner)),
+                            _ => return self.mismatch("enum", value),
+                        }
+                    }
+                    Value::None => ("None", None),
+                    Value::Some(inner) | Value::Ok(inner) | Value::Err(inner) => {
+                        (value.wrapper().unwrap(), Some(&**inner))
+                    }
+                    _ => return self.mismatch("enum", value),
+                };
+                let variant = match var.variants().iter().find(|v| v.name().make_str() == name) {
+                    Some(variant) => variant,
+                    None => return self.err(EncodeErrorKind::UnknownVariant(name.into())),
+                };
+                self.out.push(variant.index());
+                self.path.push(Cow::Borrowed(variant.name().make_str()));
+                match (value, inner, variant.fields()) {
+                    (Value::Object(_), Some(inner), fields) => self.encode_fields(inner, fields)?,
+                    (_, None, []) => {}
+                    (_, Some(inner), [field]) => {
+                        self.path.push(Cow::Borrowed("0"));
+                        self.encode(inner, field.ty().id())?;
+                        self.path.pop();
+                    }
+                    (_, inner, fields) => {
+                        return self.err(EncodeErrorKind::WrongLength {
+                            expected: fields.len(),
+                            found: inner.is_some() as usize,
+                        })
+                    }
+                }
+                self.path.pop();
+                Ok(())
+            }
+            TypeDef::Sequence(seq) => {
+                let elem = seq.type_param().id();
+                if self.is_u8(elem) {
+                    if let Some(bytes) = value.as_bytes() {
+                        Compact(bytes.len() as u32).encode_to(self.out);
+                        self.out.extend_from_slice(bytes);
+                        return Ok(());
+                    }
+                }
+                let elems = self.elements(value)?;
+                Compact(elems.len() as u32).encode_to(self.out);
+                self.encode_elements(&elems, elem)
+            }
+            TypeDef::Array(arr) => {
+                let elem = arr.type_param().id();
+                let expected = arr.len() as usize;
+                if self.is_u8(elem) {
+                    if let Some(bytes) = value.as_bytes() {
+                        if bytes.len() != expected {
+                            return self.err(EncodeErrorKind::WrongLength {
+                                expected,
+                                found: bytes.len(),
+                            });
+                        }
+                        self.out.extend_from_slice(bytes);
+                        return Ok(());
+                    }
+                }
+                let elems = self.elements(value)?;
+                if elems.len() != expected {
+                    return self.err(EncodeErrorKind::WrongLength {
+                        expected,
+                        found: elems.len(),
+                    });
+                }
+                self.encode_elements(&elems, elem)
+            }
+            TypeDef::Tuple(tuple) => {
+                let elems = self.elements(value)?;
+                if elems.len() != tuple.fields().len() {
+                    return self.err(EncodeErrorKind::WrongLength {
+                        expected: tuple.fields().len(),
+                        found: elems.len(),
+                    });
+                }
+                for (i, (elem, ty)) in elems.iter().zip(tuple.fields()).enumerate() {
+                    self.path.push(i.to_string().into());
+                    self.encode(elem, ty.id())?;
+                    self.path.pop();
+                }
+                Ok(())
+            }
+            TypeDef::Primitive(prim) => self.encode_primitive(value, prim),
+            TypeDef::Compact(inner) => {
+                let inner_id = inner.type_param().id();
+                let inner_ty = match types.resolve(inner_id) {
+                    Some(ty) => ty,
+                    None => return self.err(EncodeErrorKind::UnknownType(inner_id)),
+                };
+                let max = match inner_ty.type_def() {
+                    TypeDef::Primitive(TypeDefPrimitive::U8) => u8::MAX as u128,
+                    TypeDef::Primitive(TypeDefPrimitive::U16) => u16::MAX as u128,
+                    TypeDef::Primitive(TypeDefPrimitive::U32) => u32::MAX as u128,
+                    TypeDef::Primitive(TypeDefPrimitive::U64) => u64::MAX as u128,
+                    TypeDef::Primitive(TypeDefPrimitive::U128) => u128::MAX,
+                    other => {
+                        return self
+                            .err(EncodeErrorKind::Unsupported(format!("compact {:?}", other)))
+                    }
+                };
+                match value.as_u128() {
+                    Some(n) if n <= max => {
+                        Compact(n).encode_to(self.out);
+                        Ok(())
+                    }
+                    Some(_) => self.err(EncodeErrorKind::OutOfRange("compact".into())),
+                    None => self.mismatch("unsigned integer", value),
+                }
+            }
+            TypeDef::BitSequence(_) => match value {
+                Value::Bits(bits) => {
+                    bits.encode_to(self.out);
+                    Ok(())
+                }
+                // Without the bitvec feature bit sequences decode as raw scale.
+                Value::Scale(_) | Value::ScaleOwned(_) => {
+                    self.out
+                        .extend_from_slice(value.as_bytes().unwrap_or_default());
+                    Ok(())
+                }
+                _ => self.mismatch("bit sequence", value),
+            },
+        }
+    }
+
+    fn is_u8(&self, type_id: u32) -> bool {
+        matches!(
+            self.types.resolve(type_id).map(|ty| ty.type_def()),
+            Some(TypeDef::Primitive(TypeDefPrimitive::U8))
+        )
+    }
+
+    /// The children of a sequence-like object, in order.
+    fn elements<'v, 'scale>(
+        &self,
+        value: &'v Value<'scale>,
+    ) -> Result<Vec<&'v Value<'scale>>, EncodeError> {
+        match value {
+            Value::Object(fields) => Ok(fields
+                .iter()
+                .filter(|(name, _)| name != "_ty")
+                .map(|(_, val)| val)
+                .collect()),
+            // Byte strings given for a sequence of some other numeric type.
+            _ => self.mismatch("sequence", value),
+        }
+    }
+
+    fn encode_elements(&mut self, elems: &[&Value], elem: u32) -> Result<(), EncodeError> {
+        for (i, val) in elems.iter().enumerate() {
+            self.path.push(i.to_string().into());
+            self.encode(val, elem)?;
+            self.path.pop();
+        }
+        Ok(())
+    }
+
+    fn encode_fields(
+        &mut self,
+        value: &Value,
+        fields: &'a [Field<PortableForm>],
+    ) -> Result<(), EncodeError> {
+        if fields.is_empty() {
+            return Ok(());
+        }
+        if !matches!(value, Value::Object(_)) {
+            return self.mismatch("object", value);
+        }
+        for (i, field) in fields.iter().enumerate() {
+            let name: Cow<'a, str> = match field.name() {
+                Some(name) => Cow::Borrowed(name.make_str()),
+                None => Cow::Owned(i.to_string()),
+            };
+            let val = match value.find(&name) {
+                Some(val) => val,
+                None => return self.err(EncodeErrorKind::MissingField(name.into_owned())),
+            };
+            self.path.push(name);
+            self.encode(val, field.ty().id())?;
+            self.path.pop();
+        }
+        Ok(())
+    }
+
+    fn encode_primitive(
+        &mut self,
+        value: &Value,
+        prim: &TypeDefPrimitive,
+    ) -> Result<(), EncodeError> {
+        macro_rules! int {
+            ($t:ty, $get:ident) => {{
+                match value.$get().map(<$t>::try_from) {
+                    Some(Ok(n)) => n.encode_to(self.out),
+                    Some(Err(_)) => {
+                        return self.err(EncodeErrorKind::OutOfRange(stringify!($t).into()))
+                    }
+                    None => return self.mismatch(stringify!($t), value),
+                }
+            }};
+        }
+        match prim {
+            TypeDefPrimitive::Bool => match value {
+                Value::Bool(b) => b.encode_to(self.out),
+                _ => return self.mismatch("bool", value),
+            },
+            TypeDefPrimitive::Char => match value {
+                Value::Char(c) => (*c as u32).encode_to(self.out),
+                _ => return self.mismatch("char", value),
+            },
+            TypeDefPrimitive::Str => match value.as_str() {
+                Some(s) => s.encode_to(self.out),
+                None => return self.mismatch("str", value),
+            },
+            TypeDefPrimitive::U8 => int!(u8, as_u128),
+            TypeDefPrimitive::U16 => int!(u16, as_u128),
+            TypeDefPrimitive::U32 => int!(u32, as_u128),
+            TypeDefPrimitive::U64 => int!(u64, as_u128),
+            TypeDefPrimitive::U128 => int!(u128, as_u128),
+            TypeDefPrimitive::I8 => int!(i8, as_i128),
+            TypeDefPrimitive::I16 => int!(i16, as_i128),
+            TypeDefPrimitive::I32 => int!(i32, as_i128),
+            TypeDefPrimitive::I64 => int!(i64, as_i128),
+            TypeDefPrimitive::I128 => int!(i128, as_i128),
+            // The decoder gives 256 bit numbers as their 32 bytes.
+            TypeDefPrimitive::U256 => match value.as_u256() {
+                Some(n) => self.out.extend_from_slice(&n[..]),
+                None => match value.as_bytes() {
+                    Some(n) if n.len() == 32 => self.out.extend_from_slice(n),
+                    _ => return self.mismatch("u256", value),
+                },
+            },
+            TypeDefPrimitive::I256 => match value.as_i256() {
+                Some(n) => self.out.extend_from_slice(&n[..]),
+                None => match value.as_bytes() {
+                    Some(n) if n.len() == 32 => self.out.extend_from_slice(n),
+                    _ => return self.mismatch("i256", value),
+                },
+            },
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::make_type_id;
+    use crate::ValueBuilder;
+    use parity_scale_codec::Encode;
+
+    #[derive(Encode, scale_info::TypeInfo)]
+    enum Call {
+        #[allow(dead_code)]
+        Remark(Vec<u8>),
+        Transfer {
+            #[codec(compact)]
+            value: u128,
+            dest: [u8; 4],
+        },
+    }
+
+    #[derive(Encode, scale_info::TypeInfo)]
+    struct Block {
+        calls: Vec<Call>,
+        names: Vec<String>,
+        #[codec(compact)]
+        number: u32,
+        flag: bool,
+        data: Vec<u8>,
+    }
+
+    fn block() -> Vec<u8> {
+        Block {
+            calls: vec![
+                Call::Remark(vec![1, 2, 3]),
+                Call::Transfer {
+                    value: 1 << 70,
+                    dest: [4; 4],
+                },
+            ],
+            names: vec!["alice".into(), "bob".into()],
+            number: 1_000_000,
+            flag: true,
+            data: vec![9; 70],
+        }
+        .encode()
+    }
+
+    #[test]
+    fn round_trip() {
+        let (id, types) = make_type_id::<Block>();
+        let encoded = block();
+        let val = ValueBuilder::parse(&encoded, id, &types);
+
+        let mut out = vec![];
+        encode_value(&val, id, &types, &mut out).unwrap();
+        assert_eq!(out, encoded);
+
+        // Owned values and values that went through scale-value encode too.
+        let owned: crate::OwnedValue = val.into_owned();
+        let widened: crate::OwnedValue = scale_value::Value::<u32>::from(owned).into();
+        let mut out = vec![];
+        encode_value(&widened, id, &types, &mut out).unwrap();
+        assert_eq!(out, encoded);
+    }
+
+    #[test]
+    fn shape_mismatches() {
+        let (id, types) = make_type_id::<Block>();
+        let encoded = block();
+        let val = ValueBuilder::parse(&encoded, id, &types);
+        let err = |val: &Value| encode_value(val, id, &types, &mut vec![]).unwrap_err();
+
+        let mut bad = val.clone();
+        if let Value::Object(fields) = &mut bad {
+            fields.retain(|(name, _)| name != "flag");
+        }
+        assert_eq!(err(&bad).kind, EncodeErrorKind::MissingField("flag".into()));
+
+        let mut bad = val.clone();
+        if let Value::Object(fields) = &mut bad {
+            fields[4].1 = Value::Str("true".into());
+        }
+        let e = err(&bad);
+        assert_eq!(e.path, "flag");
+        assert_eq!(e.to_string(), "at flag: expected bool, found Str");
+
+        let (id, types) = make_type_id::<u8>();
+        let e = encode_value(&Value::U32(256), id, &types, &mut vec![]).unwrap_err();
+        assert_eq!(e.kind, EncodeErrorKind::OutOfRange("u8".into()));
+
+        let (id, types) = make_type_id::<Call>();
+        let unknown = Value::Object(Box::new(vec![
+            ("_ty".into(), Value::U32(0)),
+            ("Burn".into(), Value::Object(Box::new(vec![]))),
+        ]));
+        let e = encode_value(&unknown, id, &types, &mut vec![]).unwrap_err();
+        assert_eq!(e.kind, EncodeErrorKind::UnknownVariant("Burn".into()));
+
+        let short = Value::Object(Box::new(vec![
+            ("_ty".into(), Value::U32(0)),
+            (
+                "Transfer".into(),
+                Value::Object(Box::new(vec![
+                    ("value".into(), Value::U8(1)),
+                    ("dest".into(), Value::Scale(&[1, 2])),
+                ])),
+            ),
+        ]));
+        let e = encode_value(&short, id, &types, &mut vec![]).unwrap_err();
+        assert_eq!(e.path, "Transfer.dest");
+        assert_eq!(
+            e.kind,
+            EncodeErrorKind::WrongLength {
+                expected: 4,
+                found: 2
+            }
+        );
+    }
+}
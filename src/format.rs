@@ -0,0 +1,317 @@
+//! Configurable pretty printing of [`Value`]s.
+//!
+//! `Display` for `Value` is `ValueFormatter::new()`: one line, byte strings
+//! truncated at 100 and type ids shown as `_ty` fields.
+//!
+//! ```
+//! # use scale_borrow::{ValueBuilder, ValueFormatter};
+//! # let mut registry = scale_info::Registry::new();
+//! # let ty = registry.register_type(&scale_info::MetaType::new::<(bool, u8)>()).id();
+//! # let registry: scale_info::PortableRegistry = registry.into();
+//! # let val = ValueBuilder::parse(&[1, 2], ty, &registry);
+//! let pretty = ValueFormatter::new()
+//!     .indent(2)
+//!     .type_ids(false)
+//!     .type_names(&registry);
+//! println!("{}", pretty.format(&val));
+//! ```
+use crate::value::{i256_to_string, u256_to_string};
+use crate::{ToStr, Value};
+use core::fmt::{self, Display, Formatter, Write};
+use scale_info::PortableRegistry;
+
+#[derive(Clone, Copy, Debug)]
+pub struct ValueFormatter<'r> {
+    indent: Option<usize>,
+    truncate: Option<usize>,
+    type_ids: bool,
+    hex: bool,
+    registry: Option<&'r PortableRegistry>,
+}
+
+impl Default for ValueFormatter<'_> {
+    fn default() -> Self {
+        ValueFormatter {
+            indent: None,
+            truncate: Some(100),
+            type_ids: true,
+            hex: false,
+            registry: None,
+        }
+    }
+}
+
+/// A [`Value`] paired with the [`ValueFormatter`] to display it with.
+pub struct Formatted<'a, 'r, 'scale> {
+    value: &'a Value<'scale>,
+    formatter: &'a ValueFormatter<'r>,
+}
+
+impl<'r> ValueFormatter<'r> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Print one field per line, indented by `spaces` per level.
+    pub fn indent(mut self, spaces: usize) -> Self {
+        self.indent = Some(spaces);
+        self
+    }
+
+    /// Byte strings longer than `len` are cut short and end in `...` (which
+    /// counts towards the length). `None` never truncates.
+    pub fn truncate(mut self, len: Option<usize>) -> Self {
+        self.truncate = len;
+        self
+    }
+
+    /// Whether to print the synthetic `_ty` fields.
+    pub fn type_ids(mut self, show: bool) -> Self {
+        self.type_ids = show;
+        self
+    }
+
+    /// Print integers as `0x` hex rather than decimal (except `I256`).
+    pub fn hex(mut self, hex: bool) -> Self {
+        self.hex = hex;
+        self
+    }
+
+    /// Prefix objects with the name of their type, looked up in `registry`.
+    pub fn type_names(mut self, registry: &'r PortableRegistry) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    pub fn format<'a, 'scale>(&'a self, value: &'a Value<'scale>) -> Formatted<'a, 'r, 'scale> {
+        Formatted {
+            value,
+            formatter: self,
+        }
+    }
+
+    /// Write `value` to `out`, passing on any error from the writer.
+    pub fn write(&self, value: &Value, out: &mut dyn Write) -> fmt::Result {
+        self.write_at(value, out, 0)
+    }
+
+    fn newline(&self, out: &mut dyn Write, depth: usize) -> fmt::Result {
+        if let Some(spaces) = self.indent {
+            writeln!(out)?;
+            write!(out, "{:1$}", "", spaces * depth)?;
+        }
+        Ok(())
+    }
+
+    fn type_name(&self, value: &Value) -> Option<&'r str> {
+        let ty = self.registry?.resolve(value.type_id()?)?;
+        ty.path().segments().last().map(|s| s.make_str())
+    }
+
+    fn write_at(&self, value: &Value, out: &mut dyn Write, depth: usize) -> fmt::Result {
+        macro_rules! int {
+            ($name:literal, $n:expr) => {
+                if self.hex {
+                    write!(out, concat!($name, "({:#x})"), $n)
+                } else {
+                    write!(out, concat!($name, "({})"), $n)
+                }
+            };
+        }
+        match value {
+            Value::Object(fields) => {
+                if let Some(name) = self.type_name(value) {
+                    write!(out, "{} ", name)?;
+                }
+                let fields = fields
+                    .iter()
+                    .filter(|(name, _)| self.type_ids || name != "_ty");
+                write!(out, "{{")?;
+                let mut first = true;
+                for (name, val) in fields {
+                    if !first {
+                        write!(out, ",")?;
+                        if self.indent.is_none() {
+                            write!(out, " ")?;
+                        }
+                    }
+                    self.newline(out, depth + 1)?;
+                    write!(out, "{}: ", name)?;
+                    self.write_at(val, out, depth + 1)?;
+                    first = false;
+                }
+                if !first {
+                    self.newline(out, depth)?;
+                }
+                write!(out, "}}")
+            }
+            Value::Scale(bytes) => self.write_bytes("Scale", bytes, out),
+            Value::ScaleOwned(bytes) => self.write_bytes("ScaleOwned", bytes, out),
+            Value::U8(n) => int!("U8", n),
+            Value::U16(n) => int!("U16", n),
+            Value::U32(n) => int!("U32", n),
+            Value::U64(n) => int!("U64", n),
+            Value::U128(n) => int!("U128", **n),
+            // `{:#x}` would print the two's complement of negative numbers.
+            Value::I8(n) => self.write_signed("I8", *n as i128, out),
+            Value::I16(n) => self.write_signed("I16", *n as i128, out),
+            Value::I32(n) => self.write_signed("I32", *n as i128, out),
+            Value::I64(n) => self.write_signed("I64", *n as i128, out),
+            Value::I128(n) => self.write_signed("I128", **n, out),
+            Value::U256(_) | Value::U256Owned(_) => {
+                let n = value.as_u256().unwrap();
+                if self.hex {
+                    write!(out, "U256({})", hex_256(n))
+                } else {
+                    write!(out, "U256({})", u256_to_string(n))
+                }
+            }
+            Value::I256(_) | Value::I256Owned(_) => {
+                write!(out, "I256({})", i256_to_string(value.as_i256().unwrap()))
+            }
+            Value::None => write!(out, "None"),
+            Value::Some(inner) | Value::Ok(inner) | Value::Err(inner) => {
+                write!(out, "{}(", value.wrapper().unwrap())?;
+                self.write_at(inner, out, depth)?;
+                write!(out, ")")
+            }
+            other => write!(out, "{:?}", other),
+        }
+    }
+
+    fn write_signed(&self, name: &str, n: i128, out: &mut dyn Write) -> fmt::Result {
+        match (self.hex, n < 0) {
+            (true, true) => write!(out, "{}(-{:#x})", name, n.unsigned_abs()),
+            (true, false) => write!(out, "{}({:#x})", name, n),
+            (false, _) => write!(out, "{}({})", name, n),
+        }
+    }
+
+    fn write_bytes(&self, name: &str, bytes: &[u8], out: &mut dyn Write) -> fmt::Result {
+        match self.truncate {
+            Some(len) if bytes.len() > len => {
+                let shown = &bytes[..len.saturating_sub(3)];
+                write!(out, "{}(0x{}...)", name, hex::encode(shown))
+            }
+            _ => write!(out, "{}(0x{})", name, hex::encode(bytes)),
+        }
+    }
+}
+
+/// `0x` hex of a little endian 256 bit number, without leading zeros.
+fn hex_256(le_bytes: &[u8; 32]) -> String {
+    let mut be = *le_bytes;
+    be.reverse();
+    let digits = hex::encode(be);
+    match digits.trim_start_matches('0') {
+        "" => "0x0".into(),
+        digits => format!("0x{}", digits),
+    }
+}
+
+impl Display for Formatted<'_, '_, '_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.formatter.write(self.value, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::make_type_id;
+    use crate::ValueBuilder;
+    use parity_scale_codec::Encode;
+
+    #[derive(Encode, scale_info::TypeInfo)]
+    struct Inner {
+        n: u16,
+        id: u32,
+    }
+
+    #[derive(Encode, scale_info::TypeInfo)]
+    struct Outer {
+        inner: Inner,
+        data: Vec<u8>,
+    }
+
+    fn sample() -> (Vec<u8>, PortableRegistry, u32) {
+        let (id, types) = make_type_id::<Outer>();
+        let encoded = Outer {
+            inner: Inner { n: 255, id: 16 },
+            data: vec![1; 8],
+        }
+        .encode();
+        (encoded, types, id)
+    }
+
+    #[test]
+    fn options() {
+        let (encoded, types, id) = sample();
+        let val = ValueBuilder::parse(&encoded, id, &types);
+
+        assert_eq!(
+            ValueFormatter::new().format(&val).to_string(),
+            val.to_string()
+        );
+        assert_eq!(
+            ValueFormatter::new()
+                .type_ids(false)
+                .hex(true)
+                .truncate(Some(5))
+                .format(&val)
+                .to_string(),
+            "{inner: {n: U16(0xff), id: U32(0x10)}, data: Scale(0x0101...)}"
+        );
+        assert_eq!(
+            ValueFormatter::new()
+                .indent(2)
+                .type_ids(false)
+                .type_names(&types)
+                .format(&val)
+                .to_string(),
+            "Outer {\n  inner: Inner {\n    n: U16(255),\n    id: U32(16)\n  },\n  data: Scale(0x0101010101010101)\n}"
+        );
+        assert_eq!(
+            ValueFormatter::new()
+                .indent(2)
+                .format(&Value::Object(Box::default()))
+                .to_string(),
+            "{}"
+        );
+
+        // The decoder doesn't produce signed numbers yet.
+        assert_eq!(
+            ValueFormatter::new()
+                .hex(true)
+                .format(&Value::I32(-16))
+                .to_string(),
+            "I32(-0x10)"
+        );
+
+        let mut big = [0u8; 32];
+        big[1] = 1;
+        assert_eq!(
+            ValueFormatter::new()
+                .hex(true)
+                .format(&Value::U256(&big))
+                .to_string(),
+            "U256(0x100)"
+        );
+    }
+
+    #[test]
+    fn write_errors_propagate() {
+        struct Full;
+        impl Write for Full {
+            fn write_str(&mut self, _: &str) -> fmt::Result {
+                Err(fmt::Error)
+            }
+        }
+        let (encoded, types, id) = sample();
+        let val = ValueBuilder::parse(&encoded, id, &types);
+        assert_eq!(
+            ValueFormatter::new().write(&val, &mut Full),
+            Err(fmt::Error)
+        );
+    }
+}
@@ -0,0 +1,163 @@
+//! Constant time field lookup for big [`Value`] trees.
+//!
+//! [`Value::find`] and [`Value::get`] scan an object's fields in order, which
+//! is fine for a handful of fields but adds up for wide structs looked up in
+//! a hot loop. [`IndexedValue`] wraps a tree without copying it and gives an
+//! object a hash map of its fields the first time it's looked into, so
+//! repeated lookups cost one hash per path segment.
+//!
+//! ```
+//! # use parity_scale_codec::Encode;
+//! # use scale_borrow::ValueBuilder;
+//! # use std::collections::BTreeMap;
+//! # #[derive(Encode, scale_info::TypeInfo)]
+//! # struct Account {
+//! #     free: u64,
+//! # }
+//! # #[derive(Encode, scale_info::TypeInfo)]
+//! # struct Accounts {
+//! #     alice: Account,
+//! #     bob: Account,
+//! # }
+//! # #[derive(Encode, scale_info::TypeInfo)]
+//! # struct State {
+//! #     accounts: Accounts,
+//! # }
+//! # let mut registry = scale_info::Registry::new();
+//! # let ty = registry.register_type(&scale_info::MetaType::new::<State>()).id();
+//! # let types: scale_info::PortableRegistry = registry.into();
+//! # let accounts = Accounts { alice: Account { free: 1 }, bob: Account { free: 2 } };
+//! # let data = State { accounts }.encode();
+//! # let names = ["alice", "bob"];
+//! let val = ValueBuilder::parse(&data, ty, &types);
+//! let index = val.indexed();
+//! for name in names {
+//!     let amount = index.get(&format!("accounts.{}.free", name));
+//! #   assert!(amount.is_some());
+//! }
+//! ```
+use crate::Value;
+use std::cell::OnceCell;
+use std::collections::HashMap;
+
+/// A [`Value`] with a lazily built field index on each object.
+///
+/// Lookups give the same results as on the value itself: when field names
+/// repeat the first one wins, and `Some`, `Ok` and `Err` can be stepped
+/// through by name.
+#[derive(Debug)]
+pub struct IndexedValue<'a, 'scale> {
+    value: &'a Value<'scale>,
+    fields: OnceCell<HashMap<&'a str, IndexedValue<'a, 'scale>>>,
+}
+
+impl<'a, 'scale> IndexedValue<'a, 'scale> {
+    pub fn new(value: &'a Value<'scale>) -> Self {
+        IndexedValue {
+            value,
+            fields: OnceCell::new(),
+        }
+    }
+
+    /// The value this indexes.
+    pub fn value(&self) -> &'a Value<'scale> {
+        self.value
+    }
+
+    /// The child called `name`, as [`Value::find`].
+    pub fn find(&self, name: &str) -> Option<&Self> {
+        self.fields().get(name)
+    }
+
+    /// Follow a dotted path of field names, as [`Value::get`].
+    pub fn get(&self, path: &str) -> Option<&'a Value<'scale>> {
+        self.get_indexed(path).map(|found| found.value)
+    }
+
+    /// Like [`IndexedValue::get`] but keeps the index of what's found, for
+    /// looking up several paths below it.
+    pub fn get_indexed(&self, path: &str) -> Option<&Self> {
+        path.split('.').try_fold(self, |cur, pa| cur.find(pa))
+    }
+
+    fn fields(&self) -> &HashMap<&'a str, IndexedValue<'a, 'scale>> {
+        self.fields.get_or_init(|| {
+            let value: &'a Value<'scale> = self.value;
+            match value {
+                Value::Object(fields) => {
+                    let mut map = HashMap::with_capacity(fields.len());
+                    for (name, val) in fields.iter() {
+                        map.entry(name.as_ref())
+                            .or_insert_with(|| IndexedValue::new(val));
+                    }
+                    map
+                }
+                Value::Some(val) | Value::Ok(val) | Value::Err(val) => value
+                    .wrapper()
+                    .map(|name| (name, IndexedValue::new(val)))
+                    .into_iter()
+                    .collect(),
+                _ => HashMap::new(),
+            }
+        })
+    }
+}
+
+impl<'scale> Value<'scale> {
+    /// An [`IndexedValue`] for fast repeated lookups into this tree.
+    pub fn indexed(&self) -> IndexedValue<'_, 'scale> {
+        IndexedValue::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+
+    fn wide(width: u32) -> Value<'static> {
+        let mut fields = vec![("_ty".into(), Value::U32(0))];
+        for i in 0..width {
+            let inner = Value::Object(Box::new(vec![
+                ("_ty".into(), Value::U32(1)),
+                ("free".into(), Value::U64(i as u64)),
+                ("reserved".into(), Value::Some(Box::new(Value::U32(i)))),
+            ]));
+            fields.push((Cow::Owned(format!("acc{}", i)), inner));
+        }
+        Value::Object(Box::new(fields))
+    }
+
+    #[test]
+    fn same_as_linear_lookup() {
+        let val = wide(500);
+        let index = val.indexed();
+        for i in (0..500).step_by(7) {
+            for path in [
+                format!("acc{}.free", i),
+                format!("acc{}.reserved.Some", i),
+                format!("acc{}._ty", i),
+                format!("acc{}.missing", i),
+                format!("acc{}.free.deeper", i),
+            ] {
+                assert_eq!(index.get(&path), val.get(&path), "{}", path);
+            }
+        }
+        assert_eq!(index.get("acc500"), None);
+        assert_eq!(index.get("acc3.reserved.Ok"), None);
+
+        let acc9 = index.get_indexed("acc9").unwrap();
+        assert_eq!(acc9.get("free"), Some(&Value::U64(9)));
+        assert!(std::ptr::eq(acc9.value(), val.get("acc9").unwrap()));
+    }
+
+    #[test]
+    fn first_duplicate_wins() {
+        let val = Value::Object(Box::new(vec![
+            ("x".into(), Value::U8(1)),
+            ("x".into(), Value::U8(2)),
+        ]));
+        assert_eq!(val.indexed().get("x"), Some(&Value::U8(1)));
+        assert_eq!(val.indexed().get("x"), val.get("x"));
+    }
+}
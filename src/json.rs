@@ -0,0 +1,580 @@
+//! Type-directed conversion from JSON, for building call data from user input.
+//!
+//! The JSON accepted mirrors what [`ser`](crate::ser) produces with the
+//! default options, plus a few conveniences:
+//!
+//! * structs are objects, tuples / tuple structs / sequences are arrays and a
+//!   struct with a single unnamed field can be given as the field itself
+//! * enums are `{"Variant": fields}`, or just `"Variant"` if it has no fields
+//! * options can also be `null` or the bare value
+//! * integers can be JSON numbers or decimal strings (needed beyond 2^53)
+//! * byte sequences and arrays can be `0x` prefixed hex strings
+//! * bit sequences are arrays of bools
+//!
+//! Errors carry the dotted path of the JSON value that didn't fit, e.g.
+//! `calls.1.Transfer.dest`.
+use crate::encode::{encode_value, EncodeError, EncodeErrorKind};
+use crate::value::Wrapper;
+use crate::{OwnedValue, ToStr, Value};
+use scale_info::form::PortableForm;
+use scale_info::{Field, PortableRegistry, TypeDef, TypeDefPrimitive};
+use serde_json::Value as Json;
+use std::borrow::Cow;
+
+/// The SCALE encoding of `json` as registry type `type_id`.
+pub fn encode_json(
+    json: &Json,
+    type_id: u32,
+    types: &PortableRegistry,
+) -> Result<Vec<u8>, EncodeError> {
+    let value = value_from_json(json, type_id, types)?;
+    let mut out = vec![];
+    encode_value(&value, type_id, types, &mut out)?;
+    Ok(out)
+}
+
+/// Build the [`Value`] that `json` describes for registry type `type_id`.
+///
+/// The result has the same shape [`ValueBuilder::parse`](crate::ValueBuilder::parse)
+/// would give for the encoded bytes.
+pub fn value_from_json(
+    json: &Json,
+    type_id: u32,
+    types: &PortableRegistry,
+) -> Result<OwnedValue, EncodeError> {
+    Converter {
+        types,
+        path: vec![],
+    }
+    .convert(json, type_id)
+}
+
+struct Converter<'a> {
+    types: &'a PortableRegistry,
+    path: Vec<String>,
+}
+
+fn describe(json: &Json) -> String {
+    match json {
+        Json::Null => "null".into(),
+        Json::Bool(_) => "bool".into(),
+        Json::Number(n) => format!("number {}", n),
+        Json::String(s) => format!("string {:?}", s),
+        Json::Array(_) => "array".into(),
+        Json::Object(_) => "object".into(),
+    }
+}
+
+/// `"None"`, `{"Some": ..}` or `{"None": ..}` rather than a bare option value.
+fn is_option_variant(json: &Json) -> bool {
+    match json {
+        Json::String(name) => name == "None",
+        Json::Object(map) if map.len() == 1 => map.contains_key("Some") || map.contains_key("None"),
+        _ => false,
+    }
+}
+
+fn object(type_id: u32, fields: Vec<(String, OwnedValue)>) -> OwnedValue {
+    let mut out = vec![(Cow::Borrowed("_ty"), Value::U32(type_id))];
+    out.extend(
+        fields
+            .into_iter()
+            .map(|(name, val)| (Cow::Owned(name), val)),
+    );
+    Value::Object(Box::new(out))
+}
+
+/// Parse a decimal string into a little endian 256 bit number.
+fn parse_u256(digits: &str) -> Option<[u8; 32]> {
+    if digits.is_empty() {
+        return None;
+    }
+    let mut n = [0u8; 32];
+    for c in digits.chars() {
+        let mut carry = c.to_digit(10)?;
+        for byte in n.iter_mut() {
+            let cur = *byte as u32 * 10 + carry;
+            *byte = cur as u8;
+            carry = cur >> 8;
+        }
+        if carry != 0 {
+            return None;
+        }
+    }
+    Some(n)
+}
+
+/// Parse a possibly negative decimal string into a little endian two's
+/// complement 256 bit number.
+fn parse_i256(digits: &str) -> Option<[u8; 32]> {
+    let (negative, digits) = match digits.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, digits),
+    };
+    let magnitude = parse_u256(digits)?;
+    if !negative {
+        return if magnitude[31] & 0x80 == 0 {
+            Some(magnitude)
+        } else {
+            None
+        };
+    }
+    let mut n = [0u8; 32];
+    let mut carry = true;
+    for (out, b) in n.iter_mut().zip(magnitude) {
+        let (sum, overflow) = (!b).overflowing_add(carry as u8);
+        *out = sum;
+        carry = overflow;
+    }
+    // -0 is fine, anything else must come out negative.
+    if n[31] & 0x80 == 0 && n != [0; 32] {
+        return None;
+    }
+    Some(n)
+}
+
+impl<'a> Converter<'a> {
+    fn err<T>(&self, kind: EncodeErrorKind) -> Result<T, EncodeError> {
+        Err(EncodeError {
+            path: self.path.join("."),
+            kind,
+        })
+    }
+
+    fn mismatch<T>(&self, expected: &str, found: &Json) -> Result<T, EncodeError> {
+        self.err(EncodeErrorKind::TypeMismatch {
+            expected: expected.into(),
+            found: describe(found),
+        })
+    }
+
+    fn in_path<T>(
+        &mut self,
+        segment: impl ToString,
+        f: impl FnOnce(&mut Self) -> Result<T, EncodeError>,
+    ) -> Result<T, EncodeError> {
+        self.path.push(segment.to_string());
+        let res = f(self);
+        self.path.pop();
+        res
+    }
+
+    fn convert(&mut self, json: &Json, type_id: u32) -> Result<OwnedValue, EncodeError> {
+        let types: &'a PortableRegistry = self.types;
+        let ty = match types.resolve(type_id) {
+            Some(ty) => ty,
+            None => return self.err(EncodeErrorKind::UnknownType(type_id)),
+        };
+        match ty.type_def() {
+            TypeDef::Composite(composite) => {
+                let fields = self.fields(json, composite.fields())?;
+                Ok(object(type_id, fields))
+            }
+            TypeDef::Variant(var) => {
+                let wrapper = Wrapper::of(ty);
+                if wrapper == Wrapper::Option && !is_option_variant(json) {
+                    let some = var
+                        .variants()
+                        .iter()
+                        .find(|v| v.name().make_str() == "Some");
+                    return match (json, some.and_then(|v| v.fields().first())) {
+                        (Json::Null, _) => Ok(Value::None),
+                        (_, Some(field)) => self
+                            .in_path("Some", |this| this.convert(json, field.ty().id()))
+                            .map(|val| Value::Some(Box::new(val))),
+                        (_, None) => self.mismatch("option", json),
+                    };
+                }
+                let (name, fields) = match json {
+                    Json::String(name) => (name.as_str(), None),
+                    Json::Object(map) if map.len() == 1 => {
+                        let (name, fields) = map.iter().next().unwrap();
+                        (name.as_str(), Some(fields))
+                    }
+                    _ => return self.mismatch("enum variant", json),
+                };
+                let variant = match var.variants().iter().find(|v| v.name().make_str() == name) {
+                    Some(variant) => variant,
+                    None => return self.err(EncodeErrorKind::UnknownVariant(name.into())),
+                };
+                let fields = self.in_path(name, |this| match fields {
+                    Some(fields) => this.fields(fields, variant.fields()),
+                    None if variant.fields().is_empty() => Ok(vec![]),
+                    None => this.mismatch("variant fields", json),
+                })?;
+                let fields = vec![
+                    (Cow::Borrowed("_ty"), Value::U32(type_id)),
+                    (Cow::Owned(name.into()), object(type_id, fields)),
+                ];
+                Ok(wrapper.convert(fields))
+            }
+            TypeDef::Sequence(seq) => {
+                let elem = seq.type_param().id();
+                if self.is_u8(elem) {
+                    if let Json::String(s) = json {
+                        return self.hex(s, None);
+                    }
+                }
+                self.elements(json, type_id, elem, None)
+            }
+            TypeDef::Array(arr) => {
+                let elem = arr.type_param().id();
+                let len = arr.len() as usize;
+                if self.is_u8(elem) {
+                    if let Json::String(s) = json {
+                        return self.hex(s, Some(len));
+                    }
+                }
+                self.elements(json, type_id, elem, Some(len))
+            }
+            TypeDef::Tuple(tuple) => {
+                let elems = match json {
+                    Json::Array(elems) => elems,
+                    _ => return self.mismatch("array", json),
+                };
+                if elems.len() != tuple.fields().len() {
+                    return self.err(EncodeErrorKind::WrongLength {
+                        expected: tuple.fields().len(),
+                        found: elems.len(),
+                    });
+                }
+                let mut fields = vec![];
+                for (i, (elem, ty)) in elems.iter().zip(tuple.fields()).enumerate() {
+                    let val = self.in_path(i, |this| this.convert(elem, ty.id()))?;
+                    fields.push((i.to_string(), val));
+                }
+                Ok(object(type_id, fields))
+            }
+            TypeDef::Primitive(prim) => self.primitive(json, prim),
+            TypeDef::Compact(inner) => self.convert(json, inner.type_param().id()),
+            TypeDef::BitSequence(_) => match json {
+                Json::Array(bits) => {
+                    let mut out = scale_value::BitSequence::new();
+                    for (i, bit) in bits.iter().enumerate() {
+                        match bit {
+                            Json::Bool(b) => out.push(*b),
+                            other => return self.in_path(i, |this| this.mismatch("bool", other)),
+                        }
+                    }
+                    Ok(Value::Bits(Box::new(out)))
+                }
+                _ => self.mismatch("array of bools", json),
+            },
+        }
+    }
+
+    fn is_u8(&self, type_id: u32) -> bool {
+        matches!(
+            self.types.resolve(type_id).map(|ty| ty.type_def()),
+            Some(TypeDef::Primitive(TypeDefPrimitive::U8))
+        )
+    }
+
+    fn hex(&self, s: &str, len: Option<usize>) -> Result<OwnedValue, EncodeError> {
+        let bytes = match s.strip_prefix("0x").map(hex::decode) {
+            Some(Ok(bytes)) => bytes,
+            _ => return self.mismatch("0x prefixed hex", &Json::String(s.into())),
+        };
+        match len {
+            Some(expected) if expected != bytes.len() => self.err(EncodeErrorKind::WrongLength {
+                expected,
+                found: bytes.len(),
+            }),
+            _ => Ok(Value::ScaleOwned(Box::new(bytes))),
+        }
+    }
+
+    fn elements(
+        &mut self,
+        json: &Json,
+        type_id: u32,
+        elem: u32,
+        len: Option<usize>,
+    ) -> Result<OwnedValue, EncodeError> {
+        let elems = match json {
+            Json::Array(elems) => elems,
+            _ => return self.mismatch("array", json),
+        };
+        match len {
+            Some(expected) if expected != elems.len() => {
+                return self.err(EncodeErrorKind::WrongLength {
+                    expected,
+                    found: elems.len(),
+                })
+            }
+            _ => {}
+        }
+        let mut fields = vec![];
+        for (i, json) in elems.iter().enumerate() {
+            let val = self.in_path(i, |this| this.convert(json, elem))?;
+            fields.push((i.to_string(), val));
+        }
+        Ok(object(type_id, fields))
+    }
+
+    fn fields(
+        &mut self,
+        json: &Json,
+        fields: &'a [Field<PortableForm>],
+    ) -> Result<Vec<(String, OwnedValue)>, EncodeError> {
+        let mut out = vec![];
+        let named = fields.iter().all(|f| f.name().is_some());
+        match json {
+            _ if fields.is_empty() => {}
+            Json::Object(map) if named => {
+                for field in fields {
+                    let name = field.name().unwrap().make_str();
+                    let val = match map.get(name) {
+                        Some(val) => val,
+                        None => return self.err(EncodeErrorKind::MissingField(name.into())),
+                    };
+                    let val = self.in_path(name, |this| this.convert(val, field.ty().id()))?;
+                    out.push((name.to_string(), val));
+                }
+            }
+            // A newtype can be `[inner]` (as serialized) or just `inner`, in
+            // which case the path has no `0` segment for it.
+            _ if !named && fields.len() == 1 => {
+                let ty = fields[0].ty().id();
+                let val = match json {
+                    Json::Array(elems) if elems.len() == 1 => self
+                        .in_path(0, |this| this.convert(&elems[0], ty))
+                        .or_else(|err| self.convert(json, ty).or(Err(err))),
+                    _ => self.convert(json, ty),
+                }?;
+                out.push(("0".into(), val));
+            }
+            Json::Array(elems) if !named => {
+                if elems.len() != fields.len() {
+                    return self.err(EncodeErrorKind::WrongLength {
+                        expected: fields.len(),
+                        found: elems.len(),
+                    });
+                }
+                for (i, (elem, field)) in elems.iter().zip(fields).enumerate() {
+                    let val = self.in_path(i, |this| this.convert(elem, field.ty().id()))?;
+                    out.push((i.to_string(), val));
+                }
+            }
+            _ if named => return self.mismatch("object", json),
+            _ => return self.mismatch("array", json),
+        }
+        Ok(out)
+    }
+
+    /// A JSON number or decimal string as a `U128` or (if negative) `I128`.
+    fn integer(&self, json: &Json, expected: &str) -> Result<OwnedValue, EncodeError> {
+        let n = match json {
+            Json::Number(n) => n
+                .as_u64()
+                .map(|n| Value::U128(Box::new(n.into())))
+                .or_else(|| n.as_i64().map(|n| Value::I128(Box::new(n.into())))),
+            Json::String(s) => match s.parse::<u128>() {
+                Ok(n) => Some(Value::U128(Box::new(n))),
+                Err(_) => s.parse::<i128>().ok().map(|n| Value::I128(Box::new(n))),
+            },
+            _ => None,
+        };
+        match n {
+            Some(n) => Ok(n),
+            None => self.mismatch(expected, json),
+        }
+    }
+
+    fn primitive(&self, json: &Json, prim: &TypeDefPrimitive) -> Result<OwnedValue, EncodeError> {
+        macro_rules! int {
+            ($t:ty, $get:ident, $variant:ident) => {{
+                match self
+                    .integer(json, stringify!($t))?
+                    .$get()
+                    .map(<$t>::try_from)
+                {
+                    Some(Ok(n)) => Value::$variant(n.into()),
+                    _ => return self.err(EncodeErrorKind::OutOfRange(stringify!($t).into())),
+                }
+            }};
+        }
+        let big = |parse: fn(&str) -> Option<[u8; 32]>, expected: &str| {
+            let parsed = match json {
+                Json::Number(n) => parse(&n.to_string()),
+                Json::String(s) => parse(s),
+                _ => return self.mismatch(expected, json),
+            };
+            match parsed {
+                Some(n) => Ok(Box::new(n)),
+                None => self.err(EncodeErrorKind::OutOfRange(expected.into())),
+            }
+        };
+        Ok(match prim {
+            TypeDefPrimitive::Bool => match json {
+                Json::Bool(b) => Value::Bool(*b),
+                _ => return self.mismatch("bool", json),
+            },
+            TypeDefPrimitive::Char => {
+                let mut chars = json.as_str().unwrap_or_default().chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Value::Char(c),
+                    _ => return self.mismatch("char", json),
+                }
+            }
+            TypeDefPrimitive::Str => match json {
+                Json::String(s) => Value::Str(Cow::Owned(s.clone())),
+                _ => return self.mismatch("string", json),
+            },
+            TypeDefPrimitive::U8 => int!(u8, as_u128, U8),
+            TypeDefPrimitive::U16 => int!(u16, as_u128, U16),
+            TypeDefPrimitive::U32 => int!(u32, as_u128, U32),
+            TypeDefPrimitive::U64 => int!(u64, as_u128, U64),
+            TypeDefPrimitive::U128 => int!(u128, as_u128, U128),
+            TypeDefPrimitive::I8 => int!(i8, as_i128, I8),
+            TypeDefPrimitive::I16 => int!(i16, as_i128, I16),
+            TypeDefPrimitive::I32 => int!(i32, as_i128, I32),
+            TypeDefPrimitive::I64 => int!(i64, as_i128, I64),
+            TypeDefPrimitive::I128 => int!(i128, as_i128, I128),
+            TypeDefPrimitive::U256 => Value::U256Owned(big(parse_u256, "u256")?),
+            TypeDefPrimitive::I256 => Value::I256Owned(big(parse_i256, "i256")?),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::make_type_id;
+    use parity_scale_codec::Encode;
+    use serde_json::json;
+
+    #[derive(Encode, scale_info::TypeInfo)]
+    struct AccountId([u8; 4]);
+
+    #[derive(Encode, scale_info::TypeInfo)]
+    enum Call {
+        Remark(Vec<u8>),
+        Transfer {
+            dest: AccountId,
+            #[codec(compact)]
+            value: u128,
+        },
+        Batch(Vec<Call>),
+        Noop,
+    }
+
+    #[test]
+    fn call_data() {
+        let (id, types) = make_type_id::<Call>();
+        let json = json!({
+            "Batch": [[
+                {"Transfer": {"dest": "0x01020304", "value": "340282366920938463463374607431768211455"}},
+                {"Remark": "0xdead"},
+                "Noop",
+                {"Transfer": {"dest": [5, 6, 7, 8], "value": 10}}
+            ]]
+        });
+        let expected = Call::Batch(vec![
+            Call::Transfer {
+                dest: AccountId([1, 2, 3, 4]),
+                value: u128::MAX,
+            },
+            Call::Remark(vec![0xde, 0xad]),
+            Call::Noop,
+            Call::Transfer {
+                dest: AccountId([5, 6, 7, 8]),
+                value: 10,
+            },
+        ]);
+        assert_eq!(encode_json(&json, id, &types).unwrap(), expected.encode());
+
+        // Newtypes can also be given bare.
+        let bare = json!({"Batch": [{"Remark": "0x"}]});
+        assert_eq!(
+            encode_json(&bare, id, &types).unwrap(),
+            Call::Batch(vec![Call::Remark(vec![])]).encode()
+        );
+    }
+
+    #[test]
+    fn options() {
+        #[derive(Encode, scale_info::TypeInfo)]
+        struct Opts {
+            a: Option<u32>,
+            b: Option<u32>,
+            c: Option<u32>,
+            d: Result<u8, ()>,
+        }
+        let (id, types) = make_type_id::<Opts>();
+        let json = json!({"a": null, "b": 7, "c": {"Some": 8}, "d": {"Ok": 1}});
+        let expected = Opts {
+            a: None,
+            b: Some(7),
+            c: Some(8),
+            d: Ok(1),
+        };
+        assert_eq!(encode_json(&json, id, &types).unwrap(), expected.encode());
+
+        let val = value_from_json(&json, id, &types).unwrap();
+        assert_eq!(val.get("a"), Some(&Value::None));
+        assert_eq!(
+            val.get("b").unwrap().as_option(),
+            Some(Some(&Value::U32(7)))
+        );
+        assert_eq!(val.get("d").unwrap().as_result(), Some(Ok(&Value::U8(1))));
+    }
+
+    #[test]
+    fn numbers() {
+        let (id, types) = make_type_id::<(i8, u64, i128, bool, char, String)>();
+        let json = json!([
+            -3,
+            "18446744073709551615",
+            "-170141183460469231731687303715884105728",
+            true,
+            "x",
+            "hi"
+        ]);
+        assert_eq!(
+            encode_json(&json, id, &types).unwrap(),
+            (-3i8, u64::MAX, i128::MIN, true, 'x' as u32, "hi".to_string()).encode()
+        );
+
+        let err = encode_json(&json!([128, 0, 0, true, "x", ""]), id, &types).unwrap_err();
+        assert_eq!(err.path, "0");
+        assert_eq!(err.kind, EncodeErrorKind::OutOfRange("i8".into()));
+
+        assert_eq!(parse_u256("0"), Some([0; 32]));
+        assert_eq!(parse_u256("1x"), None);
+        let max = "115792089237316195423570985008687907853269984665640564039457584007913129639935";
+        assert_eq!(parse_u256(max), Some([0xff; 32]));
+        assert_eq!(parse_u256(&format!("{}0", max)), None);
+        assert_eq!(parse_i256("-1"), Some([0xff; 32]));
+        assert_eq!(parse_i256(max), None);
+    }
+
+    #[test]
+    fn errors_point_at_json_path() {
+        let (id, types) = make_type_id::<Call>();
+        let err = |json| encode_json(&json, id, &types).unwrap_err();
+
+        let e = err(json!({"Batch": [[{"Transfer": {"dest": "0x0102", "value": 1}}]]}));
+        assert_eq!(
+            e.to_string(),
+            "at Batch.0.0.Transfer.dest: expected 4 elements, found 2"
+        );
+
+        let e = err(json!({"Batch": [["Noop", {"Transfer": {"dest": "0x01020304"}}]]}));
+        assert_eq!(e.path, "Batch.0.1.Transfer");
+        assert_eq!(e.kind, EncodeErrorKind::MissingField("value".into()));
+
+        let e = err(json!({"Remark": "dead"}));
+        assert_eq!(
+            e.to_string(),
+            "at Remark: expected 0x prefixed hex, found string \"dead\""
+        );
+        assert_eq!(err(json!({"Remark": ["dead"]})).path, "Remark.0");
+
+        assert_eq!(
+            err(json!("Burn")).kind,
+            EncodeErrorKind::UnknownVariant("Burn".into())
+        );
+        assert_eq!(err(json!("Transfer")).path, "Transfer");
+    }
+}
@@ -8,49 +8,293 @@ pub trait VisitScale<'scale> {
     // Visit value on current object
     fn visit(
         &mut self,
-        path: &[(&'scale str, u32)],
+        path: &[(PathSegment<'scale>, u32)],
         data: &'scale [u8],
         ty: &'scale Type<PortableForm>,
         types: &'scale PortableRegistry,
     );
+
+    /// A composite, variant, sequence or array at `path` is about to have its
+    /// contents visited. Variants are entered twice: once for the enum and once
+    /// for the variant itself. Sequences and arrays of u8 are visited as a whole
+    /// rather than entered.
+    fn enter(
+        &mut self,
+        _path: &[(PathSegment<'scale>, u32)],
+        _ty_id: u32,
+        _ty: &'scale Type<PortableForm>,
+    ) {
+    }
+
+    /// Everything in the container entered at `path` has been visited.
+    fn leave(&mut self, _path: &[(PathSegment<'scale>, u32)]) {}
+
+    /// The fields of the variant at `path` (which ends with the variant's name)
+    /// have been visited. `data` is their encoding, without the variant index.
+    fn variant(&mut self, _path: &[(PathSegment<'scale>, u32)], _data: &'scale [u8]) {}
+
+    /// Whether to end the walk here. It's asked after each field or element,
+    /// and once it's true there are no more calls, not even to `leave` the
+    /// containers the walk was in.
+    fn stopped(&self) -> bool {
+        false
+    }
+}
+
+/// One step of the walker's path: the name of a field or variant from the
+/// registry, or the position of an element or unnamed field.
+///
+/// Positions are formatted into the segment itself, so a path never needs
+/// more than the stack it's on, however long the sequences in the data.
+#[derive(Clone, Copy)]
+pub struct PathSegment<'scale>(SegmentRepr<'scale>);
+
+#[derive(Clone, Copy)]
+enum SegmentRepr<'scale> {
+    Name(&'scale str),
+    /// Decimal digits at the end of `digits`, from `start`.
+    Index { digits: [u8; 20], start: u8 },
+}
+
+impl<'scale> PathSegment<'scale> {
+    pub fn name(name: &'scale str) -> Self {
+        PathSegment(SegmentRepr::Name(name))
+    }
+
+    pub fn index(index: u64) -> Self {
+        let mut digits = [0; 20];
+        let mut start = digits.len();
+        let mut rest = index;
+        loop {
+            start -= 1;
+            digits[start] = b'0' + (rest % 10) as u8;
+            rest /= 10;
+            if rest == 0 {
+                break;
+            }
+        }
+        PathSegment(SegmentRepr::Index {
+            digits,
+            start: start as u8,
+        })
+    }
+
+    /// The name from the registry, which lives as long as it does, or `None`
+    /// for a position.
+    pub fn as_name(&self) -> Option<&'scale str> {
+        match self.0 {
+            SegmentRepr::Name(name) => Some(name),
+            SegmentRepr::Index { .. } => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match &self.0 {
+            SegmentRepr::Name(name) => name,
+            SegmentRepr::Index { digits, start } => {
+                core::str::from_utf8(&digits[*start as usize..]).unwrap()
+            }
+        }
+    }
 }
+
+impl core::ops::Deref for PathSegment<'_> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq for PathSegment<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl PartialEq<str> for PathSegment<'_> {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for PathSegment<'_> {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl core::fmt::Debug for PathSegment<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl core::fmt::Display for PathSegment<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "arena")]
+pub mod arena;
 pub mod borrow_decode;
+mod cmp;
+pub mod convert;
+#[cfg(feature = "serde")]
+pub mod de;
+pub mod descale;
+pub mod diff;
+pub mod edit;
+pub mod encode;
+#[cfg(feature = "display")]
+pub mod format;
+pub mod index;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod query;
+#[cfg(feature = "serde")]
+pub mod ser;
 pub mod value;
-pub use value::{Value, ValueBuilder};
+pub mod walk;
+#[cfg(feature = "serde")]
+pub use de::from_bytes;
+pub use descale::Descale;
+pub use encode::encode_value;
+#[cfg(feature = "display")]
+pub use format::ValueFormatter;
+#[cfg(feature = "json")]
+pub use json::encode_json;
+pub use value::{OwnedValue, Value, ValueBuilder};
+pub use scale_info;
+#[cfg(feature = "derive")]
+pub use scale_borrow_derive::Descale;
+/// What `descale!` and `descale_enum!` expand to.
+#[doc(hidden)]
+pub mod __private {
+    pub use scale_borrow_derive::Descale;
+}
+// So `::scale_borrow` paths in derived code resolve in this crate's tests.
+extern crate self as scale_borrow;
 // use scale_decode::visitor::{self, TypeId};
 
+/// Declare a struct whose fields are decoded from the given paths.
+///
+/// A `*` path segment matches any field, variant or element; collect every
+/// match into a `Vec` (see [`DescaleField`](descale::DescaleField)).
+///
+/// A field can itself be a descale struct or enum, mounted at its path: its
+/// own paths are followed from there, so reusable extractors can be composed.
+///
+/// A field whose path isn't visited keeps its default, so make it an `Option`
+/// to tell "not present" from zero, or mark it `#[required]` to make parsing
+/// fail instead.
+///
+/// `#[path("who", with = f)]` sets the field to `f` applied to the value at
+/// the path, borrow-decoded as whatever `f` takes (`&[u8]` for the raw bytes).
+/// With `try_with` `f` returns a `Result` and an error fails `try_parse`. See
+/// [`convert`](descale::convert).
+///
+/// This expands to the `Descale` derive, which compiles the paths into nested
+/// `match`es on the walker's path segments, so each value the walker visits
+/// is matched against all of them in one pass. The derive (re-exported with
+/// the `derive` feature) does the same for an ordinary struct definition.
+/// Both implement [`Descale`](descale::Descale), whose `bind` checks the
+/// paths against the registry before decoding.
 #[macro_export]
 macro_rules! descale {
-    (struct $n:ident <$scale:lifetime> {
-        $(#[path($path:literal)] $fieldname:ident: $t:ty,)+
-    }) => {
-        #[derive(Default)]
+    (struct $n:ident <$scale:lifetime> { $($fields:tt)+ }) => {
+        $crate::descale!(@fields $n<$scale> [] $($fields)+);
+    };
+    // Take the fields one at a time, so `#[required]` is only accepted as
+    // written.
+    (@fields $n:ident <$scale:lifetime> [$($done:tt)*]
+        #[path($path:literal $(, $conv:ident = $with:expr)?)] #[required] $fieldname:ident: $t:ty, $($rest:tt)*
+    ) => {
+        $crate::descale!(@fields $n<$scale> [$($done)* ($fieldname: $t, [path = $path $(, $conv = ($with))?, required])] $($rest)*);
+    };
+    (@fields $n:ident <$scale:lifetime> [$($done:tt)*]
+        #[path($path:literal $(, $conv:ident = $with:expr)?)] $fieldname:ident: $t:ty, $($rest:tt)*
+    ) => {
+        $crate::descale!(@fields $n<$scale> [$($done)* ($fieldname: $t, [path = $path $(, $conv = ($with))?])] $($rest)*);
+    };
+    (@fields $n:ident <$scale:lifetime> [$(($fieldname:ident: $t:ty, [$($options:tt)*]))+]) => {
+        #[derive(Default, $crate::__private::Descale)]
+        #[descale(crate = $crate)]
         struct $n<$scale> {
-            $(pub $fieldname: $t,)+
+            $(#[descale($($options)*)] pub $fieldname: $t,)+
             _tag: std::marker::PhantomData<&$scale [u8]>
         }
+    };
+}
 
-        impl <$scale> $n<$scale> {
-            fn parse(data: &'scale [u8], top_type: UntrackedSymbol<TypeId>, types: &'scale scale_info::PortableRegistry) -> $n<$scale> {
-                let mut slf = $n::<$scale>::default();
-                crate::skeleton_decode(data, top_type.id(), &mut slf, types);
-                slf
-            }
+/// Declare an enum with an arm for each of the given variants, whose fields
+/// are decoded from paths below that variant as in [`descale!`]. Any other
+/// variant gives the `#[other]` arm, with the variant's name and the encoding
+/// of its fields.
+///
+/// ```
+/// # use parity_scale_codec::Encode;
+/// # use scale_borrow::{descale_enum, Descale};
+/// # #[allow(non_camel_case_types)]
+/// # #[derive(Encode, scale_info::TypeInfo)]
+/// # enum RuntimeCall {
+/// #     transfer { dest: Vec<u8> },
+/// #     remark(Vec<u8>),
+/// #     kill,
+/// # }
+/// # let mut registry = scale_info::Registry::new();
+/// # let ty = registry.register_type(&scale_info::MetaType::new::<RuntimeCall>()).id();
+/// # let types: scale_info::PortableRegistry = registry.into();
+/// descale_enum! {
+///     enum Call<'scale> {
+///         #[variant("transfer")]
+///         Transfer {
+///             #[path("dest")]
+///             dest: &'scale [u8],
+///         },
+///         #[variant("remark")]
+///         Remark {},
+///         #[other]
+///         Other { name: &'scale str, data: &'scale [u8] },
+///     }
+/// }
+///
+/// let calls = Call::bind(ty, &types)?;
+/// let transfer = RuntimeCall::transfer { dest: vec![1, 2] }.encode();
+/// assert!(matches!(calls.parse(&transfer), Call::Transfer { dest: [1, 2] }));
+/// let kill = RuntimeCall::kill.encode();
+/// assert!(matches!(calls.parse(&kill), Call::Other { name: "kill", .. }));
+/// # Ok::<(), scale_borrow::descale::BindError>(())
+/// ```
+///
+/// It can be parsed on its own or mounted as a field of a descale struct.
+/// Until a variant is seen it's the `#[other]` arm with an empty name, which
+/// is also its `Default`.
+#[macro_export]
+macro_rules! descale_enum {
+    (enum $n:ident <$scale:lifetime> {
+        $(
+            #[variant($variant:literal)]
+            $arm:ident { $(#[path($path:literal $(, $conv:ident = $with:expr)?)] $fieldname:ident: $t:ty,)* },
+        )+
+        #[other]
+        $other:ident { $name:ident: $name_ty:ty, $data:ident: $data_ty:ty $(,)? } $(,)?
+    }) => {
+        #[derive($crate::__private::Descale)]
+        #[descale(crate = $crate)]
+        enum $n<$scale> {
+            $(
+                #[descale(variant = $variant)]
+                $arm { $(#[descale(path = $path $(, $conv = ($with))?)] $fieldname: $t,)* },
+            )+
+            #[descale(other)]
+            $other { $name: $name_ty, $data: $data_ty },
         }
 
-        impl <'scale> VisitScale<'scale> for $n<$scale> {
-            fn visit(&mut self, current_path: &[(&'scale str,u32)], data: &'scale [u8], _ty: &'scale scale_info::Type<scale_info::form::PortableForm>, _types: &'scale PortableRegistry) {
-                $(
-                    let p: Vec<_> = $path.split('.').collect();//TODO: do earlier.
-                    // println!("visited path {:?} == {:?}", current_path, p);
-                    if current_path.len() == p.len() {
-                        let same = current_path.iter().zip(p).all(|((seg,_), p_seg)| *seg == p_seg);
-                        if same {
-                            // println!("visited path found");
-                            self.$fieldname = <$t as crate::borrow_decode::BorrowDecode>::borrow_decode(data);
-                        }
-                    }
-                )+
+        impl <$scale> ::core::default::Default for $n<$scale> {
+            fn default() -> Self {
+                <Self as $crate::descale::Descale<$scale>>::empty()
             }
         }
     };
@@ -66,7 +310,7 @@ pub fn skeleton_decode<'scale>(
 ) {
     let id = ty_id;
     let ty = types.resolve(id).unwrap();
-    let vec: Vec<(&'scale str, u32)> = vec![];
+    let vec: Vec<(PathSegment<'scale>, u32)> = vec![];
     let cursor = &mut &*data;
     semi_decode_aux(vec, cursor, ty, id, visitor, types);
 }
@@ -175,28 +419,33 @@ pub fn skeleton_decode<'scale>(
 // }
 
 
-static NUMS: &[&str] = &["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"];
 fn semi_decode_aux<'scale, V: VisitScale<'scale>>(
-    mut stack: Vec<(&'scale str, u32)>,
+    mut stack: Vec<(PathSegment<'scale>, u32)>,
     data: &mut &'scale [u8],
     ty: &'scale Type<PortableForm>,
     id: u32,
     visitor: &mut V,
     types: &'scale PortableRegistry,
-) -> Vec<(&'scale str, u32)> {
-    println!("decode {:#?} - left {}", ty.type_def(), data.len());
+) -> Vec<(PathSegment<'scale>, u32)> {
     let original_len = data.len();
     match ty.type_def() {
         TypeDef::Composite(inner) => {
+            visitor.enter(&stack, id, ty);
             for (i, field) in inner.fields().iter().enumerate() {
                 let id = field.ty().id();
                 let field_ty = types.resolve(id).unwrap();
-                let s: &'scale str = NUMS[i];
-                let fieldname: &'scale str = field.name().map(|s| s.make_str()).unwrap_or(s);
+                let fieldname = match field.name() {
+                    Some(name) => PathSegment::name(name.make_str()),
+                    None => PathSegment::index(i as u64),
+                };
                 stack.push((fieldname, id));
                 stack = semi_decode_aux(stack, data, field_ty, id, visitor, types);
                 stack.pop();
+                if visitor.stopped() {
+                    return stack;
+                }
             }
+            visitor.leave(&stack);
         }
         TypeDef::Variant(var) => {
             let (&discriminant, data_new) = data.split_first().unwrap();
@@ -207,22 +456,32 @@ fn semi_decode_aux<'scale, V: VisitScale<'scale>>(
                 .find(|v| v.index() == discriminant)
                 .unwrap();
 
-            stack.push((variant.name(), id));
+            visitor.enter(&stack, id, ty);
+            stack.push((PathSegment::name(variant.name()), id));
+            visitor.enter(&stack, id, ty);
+            let fields_data: &'scale [u8] = data;
             for (i, field) in variant.fields().iter().enumerate() {
                 let id = field.ty().id();
                 let field_ty = types.resolve(id).unwrap();
-                let s: &'scale str = NUMS[i];
 
-                let fieldname: &'scale str = if let Some(name) = field.name() {
-                    (*name).make_str()
+                let fieldname = if let Some(name) = field.name() {
+                    PathSegment::name((*name).make_str())
                 } else {
-                    s
+                    PathSegment::index(i as u64)
                 };
                 stack.push((fieldname, id));
                 stack = semi_decode_aux(stack, data, field_ty, id, visitor, types);
                 stack.pop();
+                if visitor.stopped() {
+                    stack.pop();
+                    return stack;
+                }
             }
+            let fields_len = fields_data.len() - data.len();
+            visitor.variant(&stack, &fields_data[..fields_len]);
+            visitor.leave(&stack);
             stack.pop();
+            visitor.leave(&stack);
         }
         TypeDef::Primitive(TypeDefPrimitive::Str) => {
             let len: u32 = Compact::<u32>::decode(data).unwrap().into();
@@ -268,16 +527,31 @@ fn semi_decode_aux<'scale, V: VisitScale<'scale>>(
                 visitor.visit(&stack, &data[..len as usize], ty, types);
                 *data = &data[usize::try_from(len).unwrap()..];
             } else {
-                println!("seq len = {}", len);
-                for i in NUMS.iter().take(len as usize) {
-                    // println!("i = {}", i);println!("bytes left to decode start: {:?}", &data);
-                    stack.push((i, ty_id.id()));
+                visitor.enter(&stack, id, ty);
+                for i in 0..len {
+                    stack.push((PathSegment::index(i), ty_id.id()));
                     // NB: this call must move the data slice onwards.
                     stack = semi_decode_aux(stack, data, ty_inner, ty_id.id(), visitor, types);
-                    // println!("bytes left to decode end  : {:?}", &data);
                     stack.pop();
+                    if visitor.stopped() {
+                        return stack;
+                    }
+                }
+                visitor.leave(&stack);
+            }
+        }
+        TypeDef::Tuple(tuple) => {
+            visitor.enter(&stack, id, ty);
+            for (i, field) in tuple.fields().iter().enumerate() {
+                let field_ty = types.resolve(field.id()).unwrap();
+                stack.push((PathSegment::index(i as u64), field.id()));
+                stack = semi_decode_aux(stack, data, field_ty, field.id(), visitor, types);
+                stack.pop();
+                if visitor.stopped() {
+                    return stack;
                 }
             }
+            visitor.leave(&stack);
         }
         TypeDef::Array(arr) => {
             let len: u32 = arr.len();
@@ -287,15 +561,17 @@ fn semi_decode_aux<'scale, V: VisitScale<'scale>>(
                 visitor.visit(&stack, &data[..len as usize], ty, types);
                 *data = &data[len as usize..];
             } else {
-                println!("seq len = {}", len);
-                for i in NUMS.iter().take(len as usize) {
-                    // println!("i = {}", i);println!("bytes left to decode start: {:?}", &data);
-                    stack.push((i, ty_id.id()));
+                visitor.enter(&stack, id, ty);
+                for i in 0..len as u64 {
+                    stack.push((PathSegment::index(i), ty_id.id()));
                     // NB: this call must move the data slice onwards.
                     stack = semi_decode_aux(stack, data, ty_inner, ty_id.id(), visitor, types);
-                    // println!("bytes left to decode end  : {:?}", &data);
                     stack.pop();
+                    if visitor.stopped() {
+                        return stack;
+                    }
                 }
+                visitor.leave(&stack);
             }
         }
         TypeDef::BitSequence(seq) => {
@@ -336,7 +612,15 @@ fn semi_decode_aux<'scale, V: VisitScale<'scale>>(
             panic!("don't understand a {:?}", ty.type_def());
         }
     }
-    assert!(data.len() < original_len, "failed to make any progress!");
+    // Structs, tuples and arrays can be zero sized (e.g. `()` or `[u32; 0]`).
+    let can_be_empty = matches!(
+        ty.type_def(),
+        TypeDef::Composite(_) | TypeDef::Tuple(_) | TypeDef::Array(_)
+    );
+    assert!(
+        can_be_empty || data.len() < original_len,
+        "failed to make any progress!"
+    );
     stack
 }
 
@@ -360,16 +644,17 @@ impl ToStr<String> for String {
 #[cfg(test)]
 mod tests {
     use super::value::{Value, ValueBuilder};
-    use crate::VisitScale;
+    use crate::{PathSegment, VisitScale};
     use parity_scale_codec::*;
     use scale_info::interner::UntrackedSymbol;
     use scale_info::prelude::any::TypeId;
     use scale_info::PortableRegistry;
+    use std::borrow::Cow;
     use wasm_bindgen_test::*;
 
     /// Given a type definition, return the PortableType and PortableRegistry
     /// that our decode functions expect.
-    fn make_type<T: scale_info::TypeInfo + 'static>() -> (UntrackedSymbol<TypeId>, PortableRegistry)
+    pub(crate) fn make_type<T: scale_info::TypeInfo + 'static>() -> (UntrackedSymbol<TypeId>, PortableRegistry)
     {
         let m = scale_info::MetaType::new::<T>();
         let mut types = scale_info::Registry::new();
@@ -379,6 +664,12 @@ mod tests {
         (id, portable_registry)
     }
 
+    /// [`make_type`] with the id as the `u32` most APIs take.
+    pub(crate) fn make_type_id<T: scale_info::TypeInfo + 'static>() -> (u32, PortableRegistry) {
+        let (id, types) = make_type::<T>();
+        (id.id(), types)
+    }
+
     #[wasm_bindgen_test]
     #[test]
     fn bool_test() {
@@ -468,13 +759,194 @@ mod tests {
         assert_eq!(
             val,
             Value::Object(Box::new(vec![
-                ("_ty", Value::U32(1)),
-                ("val", Value::Bool(true)),
-                ("name", Value::Str("hi val"))
+                ("_ty".into(), Value::U32(0)),
+                ("val".into(), Value::Bool(true)),
+                ("name".into(), Value::Str("hi val".into()))
             ]))
         );
     }
 
+    #[cfg(feature = "derive")]
+    #[wasm_bindgen_test]
+    #[test]
+    fn derive_descale() {
+        use crate::Descale;
+
+        #[derive(Decode, Encode, scale_info::TypeInfo)]
+        struct X {
+            val: bool,
+            name: String,
+            count: u64,
+        }
+
+        /// Docs, visibility, other derives, generics and a missing trailing
+        /// comma are all fine.
+        #[derive(Debug, Descale)]
+        pub struct XParse<'scale, T> {
+            #[descale(path = "val")]
+            pub flag: bool,
+            #[descale(path = "name")]
+            pub name: &'scale str,
+            #[descale(path = "count")]
+            count: T,
+            untouched: Option<u8>
+        }
+
+        #[derive(Descale)]
+        struct Count(#[descale(path = "count")] u64);
+
+        let encoded = X {
+            val: true,
+            name: "hi val".into(),
+            count: 7,
+        }
+        .encode();
+        let (id, types) = make_type::<X>();
+
+        let xx = XParse::<u64>::parse(&encoded, id.id(), &types);
+        assert!(xx.flag);
+        assert_eq!(xx.name, "hi val");
+        assert_eq!(xx.count, 7);
+        assert_eq!(xx.untouched, None);
+        assert_eq!(Count::parse(&encoded, id.id(), &types).0, 7);
+
+        let bound = XParse::<u64>::bind(id.id(), &types).unwrap();
+        assert_eq!(bound.parse(&encoded).name, "hi val");
+        // `count` is a u64, not a string.
+        let err = XParse::<&str>::bind(id.id(), &types).err().unwrap();
+        assert_eq!(err.to_string(), "at count: can't decode &str from u64");
+
+        #[derive(Descale)]
+        struct Typo(#[descale(path = "cuont")] u64);
+        assert!(Typo::bind(id.id(), &types).is_err());
+
+        #[derive(Descale)]
+        struct Missing {
+            #[descale(path = "count")]
+            count: Option<u64>,
+            #[descale(path = "val.0", required)]
+            _val: bool,
+        }
+        let err = Missing::try_parse(&encoded, id.id(), &types).err().unwrap();
+        assert_eq!(err.path, "val.0");
+        assert!(Missing::bind(id.id(), &types).is_err());
+
+        fn halve(n: u64) -> Result<u64, &'static str> {
+            match n % 2 {
+                0 => Ok(n / 2),
+                _ => Err("odd"),
+            }
+        }
+        #[derive(Descale)]
+        struct Converted {
+            #[descale(path = "name", with = str::len)]
+            name_len: usize,
+            #[descale(path = "count", try_with = halve)]
+            half: u64,
+        }
+        let bound = Converted::bind(id.id(), &types).unwrap();
+        let err = bound.try_parse(&encoded).err().unwrap();
+        assert_eq!(err.to_string(), "at count: conversion failed: odd");
+        let even = X {
+            val: true,
+            name: "hi".into(),
+            count: 8,
+        }
+        .encode();
+        let converted = bound.parse(&even);
+        assert_eq!((converted.name_len, converted.half), (2, 4));
+
+        #[derive(Decode, Encode, scale_info::TypeInfo)]
+        struct Outer {
+            inner: X,
+        }
+        #[derive(Descale)]
+        struct Mounted<'scale> {
+            #[descale(path = "inner")]
+            x: XParse<'scale, u64>,
+        }
+        impl Default for XParse<'_, u64> {
+            fn default() -> Self {
+                XParse::empty()
+            }
+        }
+        let encoded = Outer {
+            inner: X {
+                val: false,
+                name: "inner".into(),
+                count: 3,
+            },
+        }
+        .encode();
+        let (id, types) = make_type::<Outer>();
+        let mounted = Mounted::bind(id.id(), &types).unwrap().parse(&encoded);
+        assert_eq!((mounted.x.name, mounted.x.count), ("inner", 3));
+    }
+
+    #[cfg(feature = "derive")]
+    #[wasm_bindgen_test]
+    #[test]
+    fn derive_descale_enum() {
+        use crate::Descale;
+
+        #[derive(Decode, Encode, scale_info::TypeInfo)]
+        enum X {
+            A,
+            B(u32, u64),
+            C { val: bool },
+        }
+
+        #[derive(Debug, PartialEq, Descale)]
+        enum XParse<'scale> {
+            A,
+            #[descale(variant = "B")]
+            Pair(#[descale(path = "1")] u64),
+            #[descale(other)]
+            Unknown(&'scale str, &'scale [u8]),
+        }
+
+        #[derive(Decode, Encode, scale_info::TypeInfo)]
+        struct Outer {
+            x: X,
+        }
+        #[derive(Descale)]
+        struct Mounted<'scale> {
+            #[descale(path = "x")]
+            x: XParse<'scale>,
+        }
+        impl Default for XParse<'_> {
+            fn default() -> Self {
+                XParse::empty()
+            }
+        }
+
+        let (id, types) = make_type::<X>();
+        let bound = XParse::bind(id.id(), &types).unwrap();
+        assert_eq!(bound.parse(&X::A.encode()), XParse::A);
+        assert_eq!(bound.parse(&X::B(1, 2).encode()), XParse::Pair(2));
+        let encoded = X::C { val: true }.encode();
+        assert_eq!(bound.parse(&encoded), XParse::Unknown("C", &[1]));
+
+        let encoded = Outer { x: X::B(3, 4) }.encode();
+        let (id, types) = make_type::<Outer>();
+        let mounted = Mounted::bind(id.id(), &types).unwrap().parse(&encoded);
+        assert_eq!(mounted.x, XParse::Pair(4));
+
+        #[derive(Descale)]
+        enum Typo<'scale> {
+            #[descale(variant = "b")]
+            B,
+            #[descale(other)]
+            Other {
+                name: &'scale str,
+                data: &'scale [u8],
+            },
+        }
+        let (id, types) = make_type::<X>();
+        let err = Typo::bind(id.id(), &types).err().unwrap();
+        assert_eq!(err.to_string(), "at b: no b in the type");
+    }
+
     #[wasm_bindgen_test]
     #[test]
     fn enum_test() {
@@ -503,18 +975,62 @@ mod tests {
         assert_eq!(
             val,
             Value::Object(Box::new(vec![
-                ("_ty", Value::U32(3)),
+                ("_ty".into(), Value::U32(0)),
                 (
-                    "C",
+                    "C".into(),
                     Value::Object(Box::new(vec![
-                        ("_ty", Value::U32(0)),
-                        ("val", Value::Bool(true))
+                        ("_ty".into(), Value::U32(0)),
+                        ("val".into(), Value::Bool(true))
                     ]),)
                 )
             ]))
         );
     }
 
+    #[wasm_bindgen_test]
+    #[test]
+    fn optional_and_required_fields() {
+        use crate::descale::ParseErrorKind;
+        use crate::Descale;
+
+        #[derive(Decode, Encode, scale_info::TypeInfo)]
+        enum X {
+            A,
+            B(u32, u64),
+            C { val: bool },
+        }
+        let (id, types) = make_type::<X>();
+        let b = X::B(0, 20).encode();
+        let c = X::C { val: false }.encode();
+
+        descale! {
+            struct XParse<'scale> {
+                #[path("B.0")]
+                b: Option<u32>,
+                #[path("C.val")]
+                c: Option<bool>,
+            }
+        };
+        let xx = XParse::parse(&b, id, &types);
+        assert_eq!((xx.b, xx.c), (Some(0), None));
+        let xx = XParse::parse(&c, id, &types);
+        assert_eq!((xx.b, xx.c), (None, Some(false)));
+        assert!(XParse::bind(id.id(), &types).is_ok());
+
+        descale! {
+            struct Required<'scale> {
+                #[path("C.val")]
+                #[required]
+                val: bool,
+            }
+        };
+        assert!(!Required::try_parse(&c, id.id(), &types).unwrap().val);
+        let err = Required::try_parse(&b, id.id(), &types).err().unwrap();
+        assert_eq!(err.path, "C.val");
+        assert!(matches!(err.kind, ParseErrorKind::Missing));
+        assert_eq!(err.to_string(), "at C.val: required path not present");
+    }
+
     #[wasm_bindgen_test]
     #[test]
     fn tuple_test() {
@@ -543,13 +1059,13 @@ mod tests {
         assert_eq!(
             val,
             Value::Object(Box::new(vec![
-                ("_ty", Value::U32(1)),
+                ("_ty".into(), Value::U32(0)),
                 (
-                    "B",
+                    "B".into(),
                     Value::Object(Box::new(vec![
-                        ("_ty", Value::U32(0)),
-                        ("0", Value::U32(10)),
-                        ("1", Value::U64(20))
+                        ("_ty".into(), Value::U32(0)),
+                        ("0".into(), Value::U32(10)),
+                        ("1".into(), Value::U64(20))
                     ]),)
                 )
             ]))
@@ -584,8 +1100,11 @@ mod tests {
         assert_eq!(
             val,
             Value::Object(Box::new(vec![
-                ("_ty", Value::U32(1)),
-                ("more_scale", Value::Scale(&[1, 2, 3, 4])),
+                ("_ty".into(), Value::U32(0)),
+                (
+                    "more_scale".into(),
+                    Value::Scale(&[1, 2, 3, 4])
+                ),
             ]))
         );
     }
@@ -638,12 +1157,12 @@ mod tests {
         assert_eq!(
             val,
             Value::Object(Box::new(vec![
-                ("_ty", Value::U32(1)),
-                ("a", Value::U8(1)),
-                ("b", Value::U16(2)),
-                ("c", Value::U32(3)),
-                ("d", Value::U64(4)),
-                ("e", Value::U128(Box::new(5)))
+                ("_ty".into(), Value::U32(0)),
+                ("a".into(), Value::U8(1)),
+                ("b".into(), Value::U16(2)),
+                ("c".into(), Value::U32(3)),
+                ("d".into(), Value::U64(4)),
+                ("e".into(), Value::U128(Box::new(5)))
             ]))
         );
     }
@@ -694,25 +1213,25 @@ mod tests {
         assert_eq!(
             val,
             Value::Object(Box::new(vec![
-                ("_ty", Value::U32(3)),
+                ("_ty".into(), Value::U32(0)),
                 (
-                    "outer",
+                    "outer".into(),
                     Value::Object(Box::new(vec![
-                        ("_ty", Value::U32(1)),
+                        ("_ty".into(), Value::U32(1)),
                         (
-                            "0",
+                            "0".into(),
                             Value::Object(Box::new(vec![
-                                ("_ty", Value::U32(2)),
-                                ("val", Value::Bool(true)),
-                                ("name", Value::Str("skip me"))
+                                ("_ty".into(), Value::U32(2)),
+                                ("val".into(), Value::Bool(true)),
+                                ("name".into(), Value::Str("skip me".into()))
                             ]))
                         ),
                         (
-                            "1",
+                            "1".into(),
                             Value::Object(Box::new(vec![
-                                ("_ty", Value::U32(2)),
-                                ("val", Value::Bool(false)),
-                                ("name", Value::Str("skip meh"))
+                                ("_ty".into(), Value::U32(2)),
+                                ("val".into(), Value::Bool(false)),
+                                ("name".into(), Value::Str("skip meh".into()))
                             ]))
                         ),
                     ]))
@@ -721,6 +1240,241 @@ mod tests {
         );
     }
 
+    #[wasm_bindgen_test]
+    #[test]
+    fn owned_test() {
+        #[derive(Decode, Encode, scale_info::TypeInfo)]
+        struct X {
+            name: String,
+            more_scale: Vec<u8>,
+        }
+        let val = X {
+            name: "hi val".into(),
+            more_scale: vec![1, 2, 3, 4],
+        };
+        let encoded = val.encode();
+        let (id, types) = make_type::<X>();
+
+        let borrowed = ValueBuilder::parse(&encoded, id.id(), &types);
+        let owned: crate::OwnedValue = borrowed.clone().into_owned();
+        assert_eq!(borrowed, owned);
+        assert_eq!(owned, borrowed);
+        assert!(matches!(
+            owned.get("name"),
+            Some(Value::Str(Cow::Owned(name))) if name == "hi val"
+        ));
+        assert!(matches!(
+            owned.get("more_scale"),
+            Some(Value::ScaleOwned(bytes)) if bytes.as_slice() == [1, 2, 3, 4]
+        ));
+
+        let n = [0xff; 32];
+        let big: crate::OwnedValue = Value::U256(&n).into_owned();
+        assert!(matches!(big, Value::U256Owned(_)));
+        assert_eq!(big, Value::U256(&n));
+        let neg: crate::OwnedValue = Value::I256(&n).into_owned();
+        assert_eq!(neg, Value::I256(&n));
+        assert_ne!(neg, big);
+
+        // Outlives the input bytes and the registry.
+        drop(borrowed);
+        drop(encoded);
+        drop(types);
+        let handle = std::thread::spawn(move || {
+            owned.get("name").and_then(|v| v.as_str()).map(String::from)
+        });
+        assert_eq!(handle.join().unwrap().as_deref(), Some("hi val"));
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn empty_nodes() {
+        #[derive(Encode, scale_info::TypeInfo)]
+        enum X {
+            A,
+            #[allow(dead_code)]
+            B(u32),
+        }
+
+        #[derive(Encode, scale_info::TypeInfo)]
+        struct Empty {}
+
+        #[derive(Encode, scale_info::TypeInfo)]
+        struct Holder {
+            none: Option<u32>,
+            some: Option<u32>,
+            ok: Result<(), u8>,
+            list: Vec<u32>,
+            empty: Empty,
+            x: X,
+        }
+
+        // A top level unit variant used to panic.
+        let encoded = X::A.encode();
+        let (id, types) = make_type::<X>();
+        let val = ValueBuilder::parse(&encoded, id.id(), &types);
+        let (name, fields) = val.variant().unwrap();
+        assert_eq!(name, "A");
+        assert_eq!(fields.into_iter().count(), 1); // just the _ty
+
+        let encoded = Holder {
+            none: None,
+            some: Some(3),
+            ok: Ok(()),
+            list: vec![],
+            empty: Empty {},
+            x: X::A,
+        }
+        .encode();
+        let (id, types) = make_type::<Holder>();
+        let val = ValueBuilder::parse(&encoded, id.id(), &types);
+
+        assert_eq!(val.get("none"), Some(&Value::None));
+        assert_eq!(val.get("some.Some"), Some(&Value::U32(3)));
+        let unit = val.get("ok.Ok").unwrap();
+        assert_eq!(unit.into_iter().count(), 1);
+        assert_eq!(val.get("list").unwrap().into_iter().count(), 1);
+        assert_eq!(val.get("empty").unwrap().into_iter().count(), 1);
+        assert_eq!(val.get("x").unwrap().variant().unwrap().0, "A");
+
+        let mut out = vec![];
+        crate::encode_value(&val, id.id(), &types, &mut out).unwrap();
+        assert_eq!(out, encoded);
+
+        let encoded = ().encode();
+        let (id, types) = make_type::<()>();
+        let val = ValueBuilder::parse(&encoded, id.id(), &types);
+        assert_eq!(val.type_id(), Some(id.id()));
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn options_and_results() {
+        #[derive(Encode, scale_info::TypeInfo)]
+        struct Point {
+            x: u32,
+        }
+
+        #[derive(Encode, scale_info::TypeInfo)]
+        struct Holder {
+            nested: Option<Option<u32>>,
+            point: Option<Point>,
+            res: Result<u32, String>,
+        }
+
+        let encoded = Holder {
+            nested: Some(None),
+            point: Some(Point { x: 5 }),
+            res: Err("nope".into()),
+        }
+        .encode();
+        let (id, types) = make_type::<Holder>();
+        let val = ValueBuilder::parse(&encoded, id.id(), &types);
+
+        let nested = val.get("nested").unwrap();
+        assert_eq!(nested.as_option(), Some(Some(&Value::None)));
+        assert_eq!(nested.as_result(), None);
+        assert_eq!(val.get("nested.Some"), Some(&Value::None));
+        assert_eq!(val.get("point.Some.x"), Some(&Value::U32(5)));
+        assert_eq!(
+            val.get("res").unwrap().as_result(),
+            Some(Err(&Value::Str("nope".into())))
+        );
+        assert_eq!(val.get("res.Ok"), None);
+        assert_eq!(val.get("point.Some").unwrap().as_option(), None);
+        #[cfg(feature = "display")]
+        assert_eq!(
+            crate::ValueFormatter::new()
+                .type_ids(false)
+                .format(&val)
+                .to_string(),
+            "{nested: Some(None), point: Some({x: U32(5)}), res: Err(Str(\"nope\"))}"
+        );
+
+        let mut out = vec![];
+        crate::encode_value(&val, id.id(), &types, &mut out).unwrap();
+        assert_eq!(out, encoded);
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn duplicate_field_names() {
+        #[derive(Encode, scale_info::TypeInfo)]
+        struct Inner {
+            a: u8,
+        }
+
+        // A leaf and an object under the same name used to make the
+        // builder descend into the leaf and panic.
+        #[derive(Encode)]
+        struct Dup(u8, Inner, u8);
+        impl scale_info::TypeInfo for Dup {
+            type Identity = Self;
+            fn type_info() -> scale_info::Type {
+                scale_info::Type::builder()
+                    .path(scale_info::Path::new("Dup", module_path!()))
+                    .composite(
+                        scale_info::build::Fields::named()
+                            .field(|f| f.ty::<u8>().name("x"))
+                            .field(|f| f.ty::<Inner>().name("x"))
+                            .field(|f| f.ty::<u8>().name("x")),
+                    )
+            }
+        }
+
+        let encoded = Dup(1, Inner { a: 2 }, 3).encode();
+        let (id, types) = make_type::<Dup>();
+        let val = ValueBuilder::parse(&encoded, id.id(), &types);
+        let fields: Vec<_> = val
+            .into_iter()
+            .filter(|(name, _)| name != "_ty")
+            .map(|(name, val)| (name.as_ref(), val.clone()))
+            .collect();
+        assert_eq!(fields.len(), 3);
+        assert_eq!(fields[0], ("x", Value::U8(1)));
+        assert_eq!(fields[1].1.get("a"), Some(&Value::U8(2)));
+        assert_eq!(fields[2], ("x", Value::U8(3)));
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn events_not_paths_decide_nesting() {
+        // Paths that don't line up with the open objects are placed by the
+        // enter/leave events alone.
+        let mut builder = ValueBuilder::default();
+        let (id, types) = make_type::<u8>();
+        let ty = types.resolve(id.id()).unwrap();
+        let seg = PathSegment::name;
+        builder.enter(&[], 0, ty);
+        builder.enter(&[(seg("a"), 1)], 1, ty);
+        builder.visit(&[(seg("b"), 2), (seg("x"), 3)], &[7], ty, &types);
+        builder.leave(&[(seg("a"), 1)]);
+        builder.visit(&[(seg("a"), 1), (seg("y"), 3)], &[8], ty, &types);
+        builder.leave(&[]);
+        let val = builder.finish().unwrap();
+
+        assert_eq!(val.get("a.x"), Some(&Value::U8(7)));
+        assert_eq!(val.get("y"), Some(&Value::U8(8)));
+        assert_eq!(val.get("a").and_then(|a| a.type_id()), Some(1));
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn path_segments() {
+        assert_eq!(PathSegment::index(0), "0");
+        assert_eq!(PathSegment::index(1234), "1234");
+        assert_eq!(PathSegment::index(u64::MAX).as_str(), u64::MAX.to_string());
+        assert_eq!(PathSegment::index(7).as_name(), None);
+        assert_eq!(PathSegment::name("free").as_name(), Some("free"));
+        assert_eq!(format!("{:?}", PathSegment::index(42)), "\"42\"");
+
+        // Elements are named as they're reached, however long the sequence.
+        let encoded = vec![9u16; 300].encode();
+        let (id, types) = make_type::<Vec<u16>>();
+        let val = ValueBuilder::parse(&encoded, id.id(), &types);
+        assert_eq!(val.get("299"), Some(&Value::U16(9)));
+    }
+
     #[test]
     fn test_value() {
         assert_eq!(std::mem::size_of::<super::value::Value>(), 24); // 16 in wasm32
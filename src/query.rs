@@ -0,0 +1,381 @@
+//! A small JSONPath-like query language over [`Value`] trees.
+//!
+//! ```text
+//! events[*].event.Balances.Transfer.amount   fields, indices and wildcards
+//! outer[3]                                   the 4th element of `outer`
+//! ..who                                      every `who` field at any depth
+//! events[*].event[?Balances]                 only events that are a `Balances` variant
+//! ```
+//!
+//! Sequences, arrays and tuples are objects with the field names `0`, `1`, ...
+//! so `outer[3]` and `outer.3` are the same query. Wildcards never match the
+//! synthetic `_ty` field. `Some`, `Ok` and `Err` have their value as the one
+//! child, so `maybe.Some` and `maybe[?Some]` work as for other enums.
+use crate::Value;
+use core::fmt::{Display, Formatter};
+
+/// One step of a parsed [`Query`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Segment<'q> {
+    /// `.name` - the child field called `name`.
+    Field(&'q str),
+    /// `[n]` - the `n`th element of a sequence, array or tuple.
+    Index(usize),
+    /// `.*` or `[*]` - every child.
+    Wildcard,
+    /// `..name` - every descendant field called `name`, at any depth.
+    Descendant(&'q str),
+    /// `..*` - every descendant.
+    AnyDescendant,
+    /// `[?Name]` - keep the current value only if it's the enum variant `Name`.
+    Variant(&'q str),
+}
+
+/// Why a query string could not be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueryError {
+    /// Byte offset into the query string where parsing failed.
+    pub position: usize,
+    pub kind: QueryErrorKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum QueryErrorKind {
+    /// A `.` or `..` that isn't followed by a field name or `*`.
+    ExpectedField,
+    /// A `[` without its matching `]`.
+    UnclosedBracket,
+    /// Something in `[...]` that isn't an index, `*` or `?Variant`.
+    InvalidBracket,
+    /// A character that can't start a segment.
+    UnexpectedChar(char),
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match &self.kind {
+            QueryErrorKind::ExpectedField => {
+                write!(f, "expected a field name at {}", self.position)
+            }
+            QueryErrorKind::UnclosedBracket => write!(f, "unclosed '[' at {}", self.position),
+            QueryErrorKind::InvalidBracket => {
+                write!(f, "expected index, '*' or '?Variant' at {}", self.position)
+            }
+            QueryErrorKind::UnexpectedChar(c) => {
+                write!(f, "unexpected {:?} at {}", c, self.position)
+            }
+        }
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// A parsed query that can be run against many values.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Query<'q> {
+    segments: Vec<Segment<'q>>,
+}
+
+fn is_name_char(c: u8) -> bool {
+    !matches!(c, b'.' | b'[' | b']')
+}
+
+impl<'q> Query<'q> {
+    pub fn parse(query: &'q str) -> Result<Self, QueryError> {
+        let bytes = query.as_bytes();
+        let mut segments = vec![];
+        let mut pos = 0;
+
+        let err = |position, kind| Err(QueryError { position, kind });
+        let name_end = |start: usize| {
+            start
+                + bytes[start..]
+                    .iter()
+                    .take_while(|c| is_name_char(**c))
+                    .count()
+        };
+
+        while pos < bytes.len() {
+            match bytes[pos] {
+                b'.' if bytes.get(pos + 1) == Some(&b'.') => {
+                    let start = pos + 2;
+                    let end = name_end(start);
+                    segments.push(match &query[start..end] {
+                        "" => return err(start, QueryErrorKind::ExpectedField),
+                        "*" => Segment::AnyDescendant,
+                        name => Segment::Descendant(name),
+                    });
+                    pos = end;
+                }
+                b'.' => {
+                    let start = pos + 1;
+                    let end = name_end(start);
+                    segments.push(match &query[start..end] {
+                        "" => return err(start, QueryErrorKind::ExpectedField),
+                        "*" => Segment::Wildcard,
+                        name => Segment::Field(name),
+                    });
+                    pos = end;
+                }
+                b'[' => {
+                    let start = pos + 1;
+                    let end = match bytes[start..].iter().position(|c| *c == b']') {
+                        Some(len) => start + len,
+                        None => return err(pos, QueryErrorKind::UnclosedBracket),
+                    };
+                    let inner = &query[start..end];
+                    segments.push(if inner == "*" {
+                        Segment::Wildcard
+                    } else if let Some(variant) = inner.strip_prefix('?') {
+                        if variant.is_empty() {
+                            return err(start, QueryErrorKind::InvalidBracket);
+                        }
+                        Segment::Variant(variant)
+                    } else if let Ok(index) = inner.parse() {
+                        Segment::Index(index)
+                    } else {
+                        return err(start, QueryErrorKind::InvalidBracket);
+                    });
+                    pos = end + 1;
+                }
+                // A leading name doesn't need a dot: `outer.0` == `.outer.0`.
+                c if pos == 0 && is_name_char(c) => {
+                    let end = name_end(pos);
+                    segments.push(match &query[..end] {
+                        "*" => Segment::Wildcard,
+                        name => Segment::Field(name),
+                    });
+                    pos = end;
+                }
+                _ => {
+                    let c = query[pos..].chars().next().unwrap();
+                    return err(pos, QueryErrorKind::UnexpectedChar(c));
+                }
+            }
+        }
+        Ok(Query { segments })
+    }
+
+    pub fn segments(&self) -> &[Segment<'q>] {
+        &self.segments
+    }
+
+    /// All the values in `root` that this query selects, in document order.
+    pub fn matches<'a, 'scale>(&self, root: &'a Value<'scale>) -> Matches<'a, 'scale> {
+        let mut current = vec![root];
+        for segment in &self.segments {
+            let mut next = vec![];
+            for val in current {
+                select(segment, val, &mut next);
+            }
+            current = next;
+        }
+        Matches {
+            inner: current.into_iter(),
+        }
+    }
+}
+
+fn descendants<'a, 'scale>(
+    val: &'a Value<'scale>,
+    name: Option<&str>,
+    out: &mut Vec<&'a Value<'scale>>,
+) {
+    for (field, child) in val.children() {
+        if name.is_none() || name == Some(field) {
+            out.push(child);
+        }
+        descendants(child, name, out);
+    }
+}
+
+fn select<'a, 'scale>(segment: &Segment, val: &'a Value<'scale>, out: &mut Vec<&'a Value<'scale>>) {
+    match segment {
+        Segment::Field(name) => out.extend(val.find(name)),
+        Segment::Index(index) => out.extend(val.find(&index.to_string())),
+        Segment::Wildcard => out.extend(val.children().map(|(_, child)| child)),
+        Segment::Descendant(name) => descendants(val, Some(name), out),
+        Segment::AnyDescendant => descendants(val, None, out),
+        Segment::Variant(name) => {
+            let variant = match val {
+                Value::None => Some("None"),
+                _ => val.variant().map(|(variant, _)| variant).or(val.wrapper()),
+            };
+            if variant == Some(*name) {
+                out.push(val);
+            }
+        }
+    }
+}
+
+/// Iterator over the results of [`Query::matches`] / [`Value::query`].
+pub struct Matches<'a, 'scale> {
+    inner: std::vec::IntoIter<&'a Value<'scale>>,
+}
+
+impl<'a, 'scale> Iterator for Matches<'a, 'scale> {
+    type Item = &'a Value<'scale>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, 'scale> ExactSizeIterator for Matches<'a, 'scale> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer<'a>(who: &'a str, amount: u128) -> Value<'a> {
+        Value::Object(Box::new(vec![
+            ("_ty".into(), Value::U32(7)),
+            (
+                "Balances".into(),
+                Value::Object(Box::new(vec![
+                    ("_ty".into(), Value::U32(7)),
+                    (
+                        "Transfer".into(),
+                        Value::Object(Box::new(vec![
+                            ("_ty".into(), Value::U32(8)),
+                            ("who".into(), Value::Str(who.into())),
+                            ("amount".into(), Value::U128(Box::new(amount))),
+                        ])),
+                    ),
+                ])),
+            ),
+        ]))
+    }
+
+    fn events() -> Value<'static> {
+        let system = Value::Object(Box::new(vec![
+            ("_ty".into(), Value::U32(7)),
+            (
+                "System".into(),
+                Value::Object(Box::new(vec![
+                    ("_ty".into(), Value::U32(7)),
+                    ("who".into(), Value::Str("sys".into())),
+                ])),
+            ),
+        ]));
+        let event = |e| {
+            Value::Object(Box::new(vec![
+                ("_ty".into(), Value::U32(2)),
+                ("event".into(), e),
+            ]))
+        };
+        Value::Object(Box::new(vec![
+            ("_ty".into(), Value::U32(0)),
+            (
+                "events".into(),
+                Value::Object(Box::new(vec![
+                    ("_ty".into(), Value::U32(1)),
+                    ("0".into(), event(transfer("alice", 10))),
+                    ("1".into(), event(system)),
+                    ("2".into(), event(transfer("bob", 20))),
+                ])),
+            ),
+        ]))
+    }
+
+    #[test]
+    fn parse_segments() {
+        let q = Query::parse("events[*].event..who[?Transfer].x[3]").unwrap();
+        assert_eq!(
+            q.segments(),
+            &[
+                Segment::Field("events"),
+                Segment::Wildcard,
+                Segment::Field("event"),
+                Segment::Descendant("who"),
+                Segment::Variant("Transfer"),
+                Segment::Field("x"),
+                Segment::Index(3),
+            ]
+        );
+        assert_eq!(Query::parse("").unwrap().segments(), &[]);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let kind = |q| Query::parse(q).unwrap_err().kind;
+        assert_eq!(kind("a."), QueryErrorKind::ExpectedField);
+        assert_eq!(kind("a..[0]"), QueryErrorKind::ExpectedField);
+        assert_eq!(kind("a[0"), QueryErrorKind::UnclosedBracket);
+        assert_eq!(kind("a[x]"), QueryErrorKind::InvalidBracket);
+        assert_eq!(kind("a[?]"), QueryErrorKind::InvalidBracket);
+        assert_eq!(kind("a]"), QueryErrorKind::UnexpectedChar(']'));
+        assert_eq!(kind("]"), QueryErrorKind::UnexpectedChar(']'));
+        assert_eq!(kind("]a"), QueryErrorKind::UnexpectedChar(']'));
+        let err = Query::parse("a[0]é").unwrap_err();
+        assert_eq!(err.kind, QueryErrorKind::UnexpectedChar('é'));
+        assert_eq!(err.to_string(), "unexpected 'é' at 4");
+        assert_eq!(
+            Query::parse("a[0").unwrap_err().to_string(),
+            "unclosed '[' at 1"
+        );
+    }
+
+    #[test]
+    fn wildcards_and_indices() {
+        let val = events();
+        let amounts: Vec<_> = val
+            .query("events[*].event.Balances.Transfer.amount")
+            .unwrap()
+            .collect();
+        assert_eq!(
+            amounts,
+            vec![&Value::U128(Box::new(10)), &Value::U128(Box::new(20))]
+        );
+
+        let who: Vec<_> = val.query("events[2]..who").unwrap().collect();
+        assert_eq!(who, vec![&Value::Str("bob".into())]);
+        assert_eq!(
+            val.query("events[2]").unwrap().next(),
+            val.query("events.2").unwrap().next()
+        );
+
+        // Wildcards skip the type id.
+        assert_eq!(val.query("events.*").unwrap().len(), 3);
+        assert_eq!(val.query("events[3]").unwrap().len(), 0);
+    }
+
+    #[test]
+    fn recursive_descent_and_variants() {
+        let val = events();
+        let who: Vec<_> = val.query("..who").unwrap().collect();
+        assert_eq!(
+            who,
+            vec![
+                &Value::Str("alice".into()),
+                &Value::Str("sys".into()),
+                &Value::Str("bob".into())
+            ]
+        );
+
+        let balances = val.query("events[*].event[?Balances]").unwrap();
+        assert_eq!(balances.len(), 2);
+        assert_eq!(val.query("events[*].event[?System]..who").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn no_match_on_leaves() {
+        let val = events();
+        // Descending into a leaf is a miss, not the leaf itself.
+        assert_eq!(
+            val.query("events[0].event.Balances.Transfer.who.x")
+                .unwrap()
+                .len(),
+            0
+        );
+        assert_eq!(val.get("events.0.event.Balances.Transfer.who.x"), None);
+        assert_eq!(
+            val.get("events.0.event.Balances.Transfer.who"),
+            Some(&Value::Str("alice".into()))
+        );
+    }
+}
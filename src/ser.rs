@@ -0,0 +1,404 @@
+//! `serde::Serialize` for [`Value`], aimed at producing clean JSON.
+//!
+//! * objects become maps (without `_ty`), sequences and tuples become arrays
+//! * byte strings become `0x` prefixed hex strings
+//! * `u128`, `i128` and 256 bit numbers become decimal strings so they
+//!   survive JavaScript's doubles, as do `u64` and `i64` beyond 2^53
+//! * bit sequences become arrays of bools
+//! * enums are tagged according to [`EnumTagging`]
+use crate::value::{i256_to_string, u256_to_string};
+use crate::Value;
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+
+/// How enum values are laid out, mirroring serde's own enum representations.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EnumTagging {
+    /// `{"Transfer": {"amount": "10"}}`, or `"Transfer"` for a unit variant.
+    #[default]
+    External,
+    /// `{"type": "Transfer", "amount": "10"}`. Like other newtype variants,
+    /// `Ok` and `Err` keep their payload in a `"0"` field.
+    Internal { tag: &'static str },
+    /// `{"type": "Transfer", "value": {"amount": "10"}}`.
+    Adjacent {
+        tag: &'static str,
+        content: &'static str,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SerializeOptions {
+    pub enum_tagging: EnumTagging,
+}
+
+/// A [`Value`] paired with the [`SerializeOptions`] to serialize it with.
+pub struct Serializable<'a, 'scale> {
+    value: &'a Value<'scale>,
+    options: SerializeOptions,
+}
+
+impl<'scale> Value<'scale> {
+    /// Serialize with non-default options, e.g. internally tagged enums.
+    pub fn serializable(&self, options: SerializeOptions) -> Serializable<'_, 'scale> {
+        Serializable {
+            value: self,
+            options,
+        }
+    }
+}
+
+impl<'scale> Serialize for Value<'scale> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.serializable(SerializeOptions::default())
+            .serialize(serializer)
+    }
+}
+
+/// The fields of an object, minus `_ty`.
+fn fields<'a, 'scale>(val: &'a Value<'scale>) -> Vec<(&'a str, &'a Value<'scale>)> {
+    match val {
+        Value::Object(fields) => fields
+            .iter()
+            .filter(|(name, _)| name != "_ty")
+            .map(|(name, val)| (name.as_ref(), val))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Sequences, arrays and tuples have fields named `0`, `1`, ... An empty
+/// object could be either, so stays a map.
+fn is_seq(fields: &[(&str, &Value)]) -> bool {
+    !fields.is_empty()
+        && fields
+            .iter()
+            .enumerate()
+            .all(|(i, (name, _))| *name == i.to_string())
+}
+
+/// The largest integer a JavaScript number holds exactly.
+const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+impl<'a, 'scale> Serializable<'a, 'scale> {
+    fn with(&self, value: &'a Value<'scale>) -> Self {
+        Serializable {
+            value,
+            options: self.options,
+        }
+    }
+
+    fn serialize_fields<S: Serializer>(
+        &self,
+        fields: &[(&str, &'a Value<'scale>)],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        if is_seq(fields) {
+            let mut seq = serializer.serialize_seq(Some(fields.len()))?;
+            for (_, val) in fields {
+                seq.serialize_element(&self.with(val))?;
+            }
+            seq.end()
+        } else {
+            let mut map = serializer.serialize_map(Some(fields.len()))?;
+            for (name, val) in fields {
+                map.serialize_entry(name, &self.with(val))?;
+            }
+            map.end()
+        }
+    }
+
+    fn serialize_variant<S: Serializer>(
+        &self,
+        name: &str,
+        inner: &'a Value<'scale>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        self.serialize_tagged(name, fields(inner), &self.with(inner), serializer)
+    }
+
+    /// A variant with the given fields, whose `content` is written as a whole
+    /// where the tagging nests it.
+    fn serialize_tagged<S: Serializer, T: Serialize>(
+        &self,
+        name: &str,
+        inner_fields: Vec<(&str, &'a Value<'scale>)>,
+        content: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match self.options.enum_tagging {
+            EnumTagging::External if inner_fields.is_empty() => serializer.serialize_str(name),
+            EnumTagging::External => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(name, content)?;
+                map.end()
+            }
+            EnumTagging::Internal { tag } => {
+                let mut map = serializer.serialize_map(Some(inner_fields.len() + 1))?;
+                map.serialize_entry(tag, name)?;
+                for (name, val) in inner_fields {
+                    map.serialize_entry(name, &self.with(val))?;
+                }
+                map.end()
+            }
+            EnumTagging::Adjacent {
+                tag,
+                content: content_tag,
+            } => {
+                let len = if inner_fields.is_empty() { 1 } else { 2 };
+                let mut map = serializer.serialize_map(Some(len))?;
+                map.serialize_entry(tag, name)?;
+                if !inner_fields.is_empty() {
+                    map.serialize_entry(content_tag, content)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+impl<'a, 'scale> Serialize for Serializable<'a, 'scale> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.value {
+            Value::Object(_) => {
+                if let Some((name, inner)) = self.value.variant() {
+                    self.serialize_variant(name, inner, serializer)
+                } else {
+                    self.serialize_fields(&fields(self.value), serializer)
+                }
+            }
+            Value::Bool(b) => serializer.serialize_bool(*b),
+            Value::Char(c) => serializer.serialize_char(*c),
+            Value::Str(s) => serializer.serialize_str(s),
+            Value::Scale(bytes) => {
+                serializer.collect_str(&format_args!("0x{}", hex::encode(bytes)))
+            }
+            Value::ScaleOwned(bytes) => {
+                serializer.collect_str(&format_args!("0x{}", hex::encode(bytes.as_slice())))
+            }
+            Value::U8(n) => serializer.serialize_u8(*n),
+            Value::U16(n) => serializer.serialize_u16(*n),
+            Value::U32(n) => serializer.serialize_u32(*n),
+            Value::U64(n) if *n > MAX_SAFE_INTEGER => serializer.collect_str(n),
+            Value::U64(n) => serializer.serialize_u64(*n),
+            Value::U128(n) => serializer.collect_str(n),
+            Value::I8(n) => serializer.serialize_i8(*n),
+            Value::I16(n) => serializer.serialize_i16(*n),
+            Value::I32(n) => serializer.serialize_i32(*n),
+            Value::I64(n) if n.unsigned_abs() > MAX_SAFE_INTEGER => serializer.collect_str(n),
+            Value::I64(n) => serializer.serialize_i64(*n),
+            Value::I128(n) => serializer.collect_str(n),
+            Value::U256(_) | Value::U256Owned(_) => {
+                serializer.serialize_str(&u256_to_string(self.value.as_u256().unwrap()))
+            }
+            Value::I256(_) | Value::I256Owned(_) => {
+                serializer.serialize_str(&i256_to_string(self.value.as_i256().unwrap()))
+            }
+            Value::Bits(bits) => serializer.collect_seq(bits.iter()),
+            Value::None => serializer.serialize_none(),
+            Value::Some(val) => serializer.serialize_some(&self.with(val)),
+            Value::Ok(val) | Value::Err(val) => self.serialize_tagged(
+                self.value.wrapper().unwrap(),
+                vec![("0", val)],
+                &self.with(val),
+                serializer,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn transfer() -> Value<'static> {
+        Value::Object(Box::new(vec![
+            ("_ty".into(), Value::U32(4)),
+            (
+                "Transfer".into(),
+                Value::Object(Box::new(vec![
+                    ("_ty".into(), Value::U32(4)),
+                    ("amount".into(), Value::U128(Box::new(u128::MAX))),
+                    ("memo".into(), Value::Scale(&[0xde, 0xad])),
+                ])),
+            ),
+        ]))
+    }
+
+    #[test]
+    fn clean_json() {
+        let val = Value::Object(Box::new(vec![
+            ("_ty".into(), Value::U32(0)),
+            ("ok".into(), Value::Bool(true)),
+            ("name".into(), Value::Str("hi".into())),
+            ("small".into(), Value::U64(7)),
+            ("neg".into(), Value::I128(Box::new(-5))),
+            (
+                "list".into(),
+                Value::Object(Box::new(vec![
+                    ("_ty".into(), Value::U32(1)),
+                    ("0".into(), Value::U8(1)),
+                    ("1".into(), Value::U8(2)),
+                ])),
+            ),
+            ("event".into(), transfer()),
+        ]));
+
+        assert_eq!(
+            serde_json::to_value(&val).unwrap(),
+            json!({
+                "ok": true,
+                "name": "hi",
+                "small": 7,
+                "neg": "-5",
+                "list": [1, 2],
+                "event": {
+                    "Transfer": {
+                        "amount": "340282366920938463463374607431768211455",
+                        "memo": "0xdead"
+                    }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn safe_integers_and_maps() {
+        let obj = |fields: Vec<(&'static str, Value<'static>)>| {
+            let mut all = vec![("_ty".into(), Value::U32(1))];
+            all.extend(fields.into_iter().map(|(name, val)| (name.into(), val)));
+            Value::Object(Box::new(all))
+        };
+        let val = obj(vec![
+            ("safe", Value::U64(MAX_SAFE_INTEGER)),
+            ("unsafe", Value::U64(MAX_SAFE_INTEGER + 1)),
+            ("neg_safe", Value::I64(-(MAX_SAFE_INTEGER as i64))),
+            ("min", Value::I64(i64::MIN)),
+            ("empty", obj(vec![])),
+            ("gap", obj(vec![("0", Value::U8(1)), ("2", Value::U8(2))])),
+        ]);
+        assert_eq!(
+            serde_json::to_value(&val).unwrap(),
+            json!({
+                "safe": 9007199254740991u64,
+                "unsafe": "9007199254740992",
+                "neg_safe": -9007199254740991i64,
+                "min": "-9223372036854775808",
+                "empty": {},
+                "gap": {"0": 1, "2": 2}
+            })
+        );
+    }
+
+    #[test]
+    fn enum_tagging() {
+        let val = transfer();
+        let internal = SerializeOptions {
+            enum_tagging: EnumTagging::Internal { tag: "type" },
+        };
+        assert_eq!(
+            serde_json::to_value(val.serializable(internal)).unwrap(),
+            json!({
+                "type": "Transfer",
+                "amount": "340282366920938463463374607431768211455",
+                "memo": "0xdead"
+            })
+        );
+
+        let adjacent = SerializeOptions {
+            enum_tagging: EnumTagging::Adjacent {
+                tag: "type",
+                content: "value",
+            },
+        };
+        assert_eq!(
+            serde_json::to_value(val.serializable(adjacent)).unwrap(),
+            json!({
+                "type": "Transfer",
+                "value": {
+                    "amount": "340282366920938463463374607431768211455",
+                    "memo": "0xdead"
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn options_and_results() {
+        let val = Value::Object(Box::new(vec![
+            ("_ty".into(), Value::U32(0)),
+            ("none".into(), Value::None),
+            ("some".into(), Value::Some(Box::new(Value::U8(1)))),
+            ("ok".into(), Value::Ok(Box::new(transfer()))),
+            ("err".into(), Value::Err(Box::new(Value::Str("bad".into())))),
+        ]));
+        assert_eq!(
+            serde_json::to_value(&val).unwrap(),
+            json!({
+                "none": null,
+                "some": 1,
+                "ok": {"Ok": {"Transfer": {
+                    "amount": "340282366920938463463374607431768211455",
+                    "memo": "0xdead"
+                }}},
+                "err": {"Err": "bad"}
+            })
+        );
+
+        let results = Value::Object(Box::new(vec![
+            ("_ty".into(), Value::U32(0)),
+            ("ok".into(), Value::Ok(Box::new(Value::U8(1)))),
+            ("err".into(), Value::Err(Box::new(Value::Str("bad".into())))),
+        ]));
+        let internal = SerializeOptions {
+            enum_tagging: EnumTagging::Internal { tag: "type" },
+        };
+        assert_eq!(
+            serde_json::to_value(results.serializable(internal)).unwrap(),
+            json!({
+                "ok": {"type": "Ok", "0": 1},
+                "err": {"type": "Err", "0": "bad"}
+            })
+        );
+        let adjacent = SerializeOptions {
+            enum_tagging: EnumTagging::Adjacent {
+                tag: "type",
+                content: "value",
+            },
+        };
+        assert_eq!(
+            serde_json::to_value(results.serializable(adjacent)).unwrap(),
+            json!({
+                "ok": {"type": "Ok", "value": 1},
+                "err": {"type": "Err", "value": "bad"}
+            })
+        );
+    }
+
+    #[test]
+    fn big_numbers() {
+        let mut n = [0u8; 32];
+        assert_eq!(u256_to_string(&n), "0");
+        n[0] = 1;
+        n[16] = 1; // 2^128 + 1
+        assert_eq!(
+            u256_to_string(&n),
+            "340282366920938463463374607431768211457"
+        );
+        assert_eq!(
+            u256_to_string(&[0xff; 32]),
+            "115792089237316195423570985008687907853269984665640564039457584007913129639935"
+        );
+        assert_eq!(i256_to_string(&[0xff; 32]), "-1");
+        assert_eq!(
+            i256_to_string(&n),
+            "340282366920938463463374607431768211457"
+        );
+
+        let bits: scale_value::BitSequence = [true, false].into_iter().collect();
+        assert_eq!(
+            serde_json::to_value(Value::Bits(Box::new(bits))).unwrap(),
+            json!([true, false])
+        );
+    }
+}
@@ -3,21 +3,33 @@ use scale_info::PortableRegistry;
 use scale_info::TypeDef;
 use scale_info::TypeDefPrimitive;
 
+#[cfg(feature = "display")]
+use crate::format::ValueFormatter;
+use crate::query::{Matches, Query, QueryError};
+use crate::PathSegment;
 #[cfg(feature = "display")]
 use core::fmt::{Display, Formatter};
+use std::borrow::Cow;
 
 /// The underlying shape of a given value.
-#[derive(Clone, Debug, PartialEq)]
+///
+/// Borrowed and owned forms of the same data (e.g. `Scale` and `ScaleOwned`)
+/// compare equal. `Value` is also `Eq`, `Hash` and `Ord` so it can be used as
+/// a map key; its `Ord` impl documents the ordering.
+#[derive(Clone, Debug)]
 pub enum Value<'scale> {
     /// A named or unnamed struct-like, array-like or tuple-like set of values.
-    Object(Box<Vec<(&'scale str, Value<'scale>)>>), // Could this be an array rather than a vec?
+    ///
+    /// Field names borrow from the registry unless the value has been made owned.
+    Object(Box<Vec<(Cow<'scale, str>, Value<'scale>)>>), // Could this be an array rather than a vec?
     // // UnamedComposite(&'scale Vec<Value<T>>)
     // /// An enum variant.
     // Variant(&'scale (&'scale str, &'scale Value<'scale>)),
     // Truth
     Bool(bool),
     Char(char),
-    Str(&'scale str),
+    /// Borrows from the input bytes unless the value has been made owned.
+    Str(Cow<'scale, str>),
     Scale(&'scale [u8]),
     // Escape hatch for when you can't borrow.
     ScaleOwned(Box<Vec<u8>>),
@@ -36,86 +48,57 @@ pub enum Value<'scale> {
     U256(&'scale [u8; 32]),
     /// A signed 256 bit number (internally represented as a 32 byte array).
     I256(&'scale [u8; 32]),
+    // Escape hatches for when you can't borrow.
+    U256Owned(Box<[u8; 32]>),
+    I256Owned(Box<[u8; 32]>),
 
-    #[cfg(feature = "bitvec")]
+    /// Only produced by the decoder with the `bitvec` feature; without it bit
+    /// sequences decode as `Scale`.
     Bits(Box<scale_value::BitSequence>),
+
+    /// `Option::None`. Options and results are recognised by their type path;
+    /// other enums are objects (see [`Value::variant`]).
+    None,
+    /// `Option::Some`, holding the value itself rather than a `0` field.
+    Some(Box<Value<'scale>>),
+    Ok(Box<Value<'scale>>),
+    Err(Box<Value<'scale>>),
 }
 
 #[cfg(feature = "display")]
 impl<'scale> Display for Value<'scale> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
-        const TRUNC_LEN: usize = 100;
-        match self {
-            Self::Object(contents) => {
-                write!(f, "{{").unwrap();
-                let mut first = true;
-                for (k, v) in contents.iter() {
-                    if !first {
-                        write!(f, ", ").unwrap();
-                    }
-                    k.fmt(f).unwrap();
-                    write!(f, ": ").unwrap();
-                    v.fmt(f).unwrap();
-                    first = false;
-                }
-                write!(f, "}}").unwrap();
-            }
-            Self::Scale(slice) => {
-                if slice.len() <= TRUNC_LEN {
-                    write!(f, "Scale(0x{})", hex::encode(slice)).unwrap();
-                } else {
-                    write!(f, "Scale(0x{}...)", hex::encode(&slice[..TRUNC_LEN - 3])).unwrap();
-                }
-            }
-            Self::ScaleOwned(v) => {
-                if v.len() <= TRUNC_LEN {
-                    write!(f, "ScaleOwned(0x{})", hex::encode(v.as_slice())).unwrap();
-                } else {
-                    write!(
-                        f,
-                        "ScaleOwned(0x{}...)",
-                        hex::encode(&v.as_slice()[..TRUNC_LEN - 3])
-                    )
-                    .unwrap();
-                }
-            }
-            _ => <Self as core::fmt::Debug>::fmt(self, f).unwrap(),
-        };
-        Ok(())
+        ValueFormatter::new().write(self, f)
     }
 }
 
 impl<'a, 'scale> IntoIterator for &'a Value<'scale> {
-    type Item = &'a (&'scale str, Value<'scale>);
-    type IntoIter = core::slice::Iter<'a, (&'scale str, Value<'scale>)>;
+    type Item = &'a (Cow<'scale, str>, Value<'scale>);
+    type IntoIter = core::slice::Iter<'a, (Cow<'scale, str>, Value<'scale>)>;
 
+    /// The fields of an object, `_ty` included. Other values have none; see
+    /// [`Value::walk`] and [`Value::leaves`] to traverse a whole tree.
     fn into_iter(self) -> Self::IntoIter {
-        if let Value::Object(ref vals) = *self {
-            vals.iter()
-        } else {
-            debug_assert!(false); // This is not a good sign.
-            todo!();
-            // vec![].iter()
+        match self {
+            Value::Object(vals) => vals.iter(),
+            _ => [].iter(),
         }
     }
 }
 
 impl<'scale> Value<'scale> {
-    pub fn get(&self, path: &str) -> Option<&Value> {
-        let p: Vec<_> = path.split('.').collect();
-        let mut cur = self;
-
-        for pa in p {
-            if let Value::Object(fields) = cur {
-                if let Some((_, sub_val)) = fields.iter().find(|(name, _)| *name == pa) {
-                    cur = sub_val;
-                } else {
-                    return None;
-                }
-            }
-        }
+    /// Follow a dotted path of field names, e.g. `"outer.0.val"`.
+    ///
+    /// Returns `None` if any segment is missing or tries to step into a
+    /// non-object. See [`Value::query`] for wildcards and recursive descent.
+    pub fn get(&self, path: &str) -> Option<&Value<'scale>> {
+        path.split('.').try_fold(self, |cur, pa| cur.find(pa))
+    }
 
-        Some(cur)
+    /// Run a [`Query`](crate::query::Query) such as `"events[*].event..who"`,
+    /// returning every match in document order.
+    pub fn query<'a>(&'a self, query: &str) -> Result<Matches<'a, 'scale>, QueryError> {
+        Ok(Query::parse(query)?.matches(self))
     }
 
     // Assume that this is an object with just one field. TODO! rename only()
@@ -123,7 +106,7 @@ impl<'scale> Value<'scale> {
         if let Self::Object(fields) = self {
             if fields.len() == 1 {
                 let (name, val) = &fields[0];
-                Some((name, val))
+                Some((name.as_ref(), val))
             } else {
                 None
             }
@@ -132,11 +115,19 @@ impl<'scale> Value<'scale> {
         }
     }
 
+    #[deprecated(
+        note = "use `Value::get`, or `Value::query` with `[?Variant]` to check enum variants"
+    )]
+    #[allow(deprecated)]
     pub fn only2(&'scale self) -> Option<(&'scale str, &'scale str, &'scale Self)> {
         self.only()
             .and_then(|(head, tail)| tail.only().map(|(second, tail)| (head, second, tail)))
     }
 
+    #[deprecated(
+        note = "use `Value::get`, or `Value::query` with `[?Variant]` to check enum variants"
+    )]
+    #[allow(deprecated)]
     pub fn only3(&'scale self) -> Option<(&'scale str, &'scale str, &'scale str, &'scale Self)> {
         self.only2().and_then(|(first, second, tail)| {
             tail.only()
@@ -144,6 +135,10 @@ impl<'scale> Value<'scale> {
         })
     }
 
+    #[deprecated(
+        note = "use `Value::get`, or `Value::query` with `[?Variant]` to check enum variants"
+    )]
+    #[allow(deprecated)]
     pub fn expect(&'scale self, expect1: &str) -> Option<&'scale Self> {
         self.only().and_then(|(head, tail)| {
             if head != expect1 {
@@ -153,10 +148,18 @@ impl<'scale> Value<'scale> {
         })
     }
 
+    #[deprecated(
+        note = "use `Value::get`, or `Value::query` with `[?Variant]` to check enum variants"
+    )]
+    #[allow(deprecated)]
     pub fn expect2(&'scale self, expect1: &str, expect2: &str) -> Option<&'scale Self> {
         self.expect(expect1).and_then(|tail| tail.expect(expect2))
     }
 
+    #[deprecated(
+        note = "use `Value::get`, or `Value::query` with `[?Variant]` to check enum variants"
+    )]
+    #[allow(deprecated)]
     pub fn expect3(
         &'scale self,
         expect1: &str,
@@ -167,6 +170,10 @@ impl<'scale> Value<'scale> {
             .and_then(|tail| tail.expect(expect3))
     }
 
+    #[deprecated(
+        note = "use `Value::get`, or `Value::query` with `[?Variant]` to check enum variants"
+    )]
+    #[allow(deprecated)]
     pub fn expect4(
         &'scale self,
         expect1: &str,
@@ -178,17 +185,56 @@ impl<'scale> Value<'scale> {
             .and_then(|tail| tail.expect(expect4))
     }
 
-    pub fn find(&'scale self, find1: &str) -> Option<&'scale Self> {
-        if let Self::Object(fields) = self {
-            for (field, val) in fields.iter() {
-                if *field == find1 {
-                    return Some(val);
+    pub fn find(&self, find1: &str) -> Option<&Self> {
+        match self {
+            Self::Object(fields) => {
+                for (field, val) in fields.iter() {
+                    if *field == find1 {
+                        return Some(val);
+                    }
                 }
             }
+            // So `Some`, `Ok` and `Err` work in paths like other variants.
+            Self::Some(val) | Self::Ok(val) | Self::Err(val) if self.wrapper() == Some(find1) => {
+                return Some(val)
+            }
+            _ => {}
         }
         None
     }
 
+    /// The variant name of a `Some`, `Ok` or `Err`.
+    pub(crate) fn wrapper(&self) -> Option<&'static str> {
+        match self {
+            Self::Some(_) => Some("Some"),
+            Self::Ok(_) => Some("Ok"),
+            Self::Err(_) => Some("Err"),
+            _ => None,
+        }
+    }
+
+    /// `Some(Some(val))` / `Some(None)` if this is an `Option`.
+    pub fn as_option(&self) -> Option<Option<&Self>> {
+        match self {
+            Self::None => Some(None),
+            Self::Some(val) => Some(Some(val)),
+            _ => None,
+        }
+    }
+
+    /// `Some(Ok(val))` / `Some(Err(err))` if this is a `Result`.
+    pub fn as_result(&self) -> Option<Result<&Self, &Self>> {
+        match self {
+            Self::Ok(val) => Some(Ok(val)),
+            Self::Err(err) => Some(Err(err)),
+            _ => None,
+        }
+    }
+
+    #[deprecated(
+        note = "use `Value::get`, or `Value::query` with `[?Variant]` to check enum variants"
+    )]
+    #[allow(deprecated)]
     pub fn find2(&'scale self, find1: &str, find2: &str) -> Option<&'scale Self> {
         self.find(find1).and_then(|val| {
             if let Self::Object(fields) = val {
@@ -201,11 +247,240 @@ impl<'scale> Value<'scale> {
             None
         })
     }
+
+    /// The string of a `Str`.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// The bytes of a `Scale` or `ScaleOwned`.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Self::Scale(bytes) => Some(bytes),
+            Self::ScaleOwned(bytes) => Some(bytes.as_slice()),
+            _ => None,
+        }
+    }
+
+    /// The little-endian bytes of a `U256` or `U256Owned`.
+    pub fn as_u256(&self) -> Option<&[u8; 32]> {
+        match self {
+            Self::U256(n) => Some(n),
+            Self::U256Owned(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// The little-endian bytes of an `I256` or `I256Owned`.
+    pub fn as_i256(&self) -> Option<&[u8; 32]> {
+        match self {
+            Self::I256(n) => Some(n),
+            Self::I256Owned(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// Any integer that is non-negative, widened to `u128`.
+    pub fn as_u128(&self) -> Option<u128> {
+        match self {
+            Self::U8(n) => Some(*n as u128),
+            Self::U16(n) => Some(*n as u128),
+            Self::U32(n) => Some(*n as u128),
+            Self::U64(n) => Some(*n as u128),
+            Self::U128(n) => Some(**n),
+            Self::I8(_) | Self::I16(_) | Self::I32(_) | Self::I64(_) | Self::I128(_) => {
+                self.as_i128().and_then(|n| u128::try_from(n).ok())
+            }
+            _ => None,
+        }
+    }
+
+    /// Any integer that fits in an `i128`.
+    pub fn as_i128(&self) -> Option<i128> {
+        match self {
+            Self::I8(n) => Some(*n as i128),
+            Self::I16(n) => Some(*n as i128),
+            Self::I32(n) => Some(*n as i128),
+            Self::I64(n) => Some(*n as i128),
+            Self::I128(n) => Some(**n),
+            Self::U128(n) => i128::try_from(**n).ok(),
+            Self::U8(_) | Self::U16(_) | Self::U32(_) | Self::U64(_) => {
+                self.as_u128().map(|n| n as i128)
+            }
+            _ => None,
+        }
+    }
+
+    /// The registry type id of an object (its `_ty` field).
+    pub fn type_id(&self) -> Option<u32> {
+        match self.find("_ty") {
+            Some(Self::U32(id)) => Some(*id),
+            _ => None,
+        }
+    }
+
+    /// If this is an enum value, the name and fields of the variant.
+    ///
+    /// An enum is an object with a single field named after the variant whose
+    /// value is an object sharing the enum's type id (a struct can't contain a
+    /// field of its own type, so this is unambiguous).
+    pub fn variant(&self) -> Option<(&str, &Self)> {
+        if let Self::Object(fields) = self {
+            let mut fields = fields.iter().filter(|(name, _)| name != "_ty");
+            if let (Some((name, inner)), None) = (fields.next(), fields.next()) {
+                if inner.type_id().is_some() && inner.type_id() == self.type_id() {
+                    return Some((name.as_ref(), inner));
+                }
+            }
+        }
+        None
+    }
+
+    /// Copy everything borrowed from the input bytes or the registry so the
+    /// value can outlive them.
+    pub fn into_owned(self) -> OwnedValue {
+        match self {
+            Self::Object(fields) => Value::Object(Box::new(
+                fields
+                    .into_iter()
+                    .map(|(name, val)| (Cow::Owned(name.into_owned()), val.into_owned()))
+                    .collect(),
+            )),
+            Self::Bool(b) => Value::Bool(b),
+            Self::Char(c) => Value::Char(c),
+            Self::Str(s) => Value::Str(Cow::Owned(s.into_owned())),
+            Self::Scale(bytes) => Value::ScaleOwned(Box::new(bytes.to_vec())),
+            Self::ScaleOwned(bytes) => Value::ScaleOwned(bytes),
+            Self::U8(n) => Value::U8(n),
+            Self::U16(n) => Value::U16(n),
+            Self::U32(n) => Value::U32(n),
+            Self::U64(n) => Value::U64(n),
+            Self::U128(n) => Value::U128(n),
+            Self::I8(n) => Value::I8(n),
+            Self::I16(n) => Value::I16(n),
+            Self::I32(n) => Value::I32(n),
+            Self::I64(n) => Value::I64(n),
+            Self::I128(n) => Value::I128(n),
+            Self::U256(n) => Value::U256Owned(Box::new(*n)),
+            Self::U256Owned(n) => Value::U256Owned(n),
+            Self::I256(n) => Value::I256Owned(Box::new(*n)),
+            Self::I256Owned(n) => Value::I256Owned(n),
+            Self::Bits(bits) => Value::Bits(bits),
+            Self::None => Value::None,
+            Self::Some(val) => Value::Some(Box::new(val.into_owned())),
+            Self::Ok(val) => Value::Ok(Box::new(val.into_owned())),
+            Self::Err(val) => Value::Err(Box::new(val.into_owned())),
+        }
+    }
+}
+
+/// A [`Value`] that doesn't borrow from anything, so can be sent between
+/// threads or kept after the input buffer is gone.
+pub type OwnedValue = Value<'static>;
+
+impl<'a, 'b> PartialEq<Value<'b>> for Value<'a> {
+    fn eq(&self, other: &Value<'b>) -> bool {
+        use Value::*;
+        match (self, other) {
+            (Object(a), Object(b)) => {
+                a.len() == b.len()
+                    && a.iter()
+                        .zip(b.iter())
+                        .all(|((name_a, a), (name_b, b))| name_a == name_b && a == b)
+            }
+            (Str(a), Str(b)) => a == b,
+            (Scale(_) | ScaleOwned(_), Scale(_) | ScaleOwned(_)) => {
+                self.as_bytes() == other.as_bytes()
+            }
+            (Bool(a), Bool(b)) => a == b,
+            (Char(a), Char(b)) => a == b,
+            (U8(a), U8(b)) => a == b,
+            (U16(a), U16(b)) => a == b,
+            (U32(a), U32(b)) => a == b,
+            (U64(a), U64(b)) => a == b,
+            (U128(a), U128(b)) => a == b,
+            (I8(a), I8(b)) => a == b,
+            (I16(a), I16(b)) => a == b,
+            (I32(a), I32(b)) => a == b,
+            (I64(a), I64(b)) => a == b,
+            (I128(a), I128(b)) => a == b,
+            (U256(_) | U256Owned(_), U256(_) | U256Owned(_)) => self.as_u256() == other.as_u256(),
+            (I256(_) | I256Owned(_), I256(_) | I256Owned(_)) => self.as_i256() == other.as_i256(),
+            (Bits(a), Bits(b)) => a == b,
+            (None, None) => true,
+            (Some(a), Some(b)) | (Ok(a), Ok(b)) | (Err(a), Err(b)) => **a == **b,
+            _ => false,
+        }
+    }
 }
 
+pub(crate) type Fields<'scale> = Vec<(Cow<'scale, str>, Value<'scale>)>;
+
+/// Builds a [`Value`] from the walker's events, keeping a stack of the
+/// objects that are still being filled so each value is placed in constant
+/// time.
 #[derive(Default)]
 pub struct ValueBuilder<'scale> {
     root: Option<Value<'scale>>,
+    /// Objects entered but not yet left, outermost first, and whether each is
+    /// an `Option` or `Result` to be turned into its native form when left.
+    open: Vec<(Cow<'scale, str>, Fields<'scale>, Wrapper)>,
+}
+
+/// Whether a type is an `Option` or `Result`, which get a native [`Value`] form.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Wrapper {
+    No,
+    Option,
+    Result,
+}
+
+impl Wrapper {
+    pub(crate) fn of(ty: &scale_info::Type<scale_info::form::PortableForm>) -> Self {
+        match ty.path().segments() {
+            [name] if *name == "Option" => Wrapper::Option,
+            [name] if *name == "Result" => Wrapper::Result,
+            _ => Wrapper::No,
+        }
+    }
+
+    /// The native form of a finished `{_ty, Variant: {_ty, 0?}}` object, or
+    /// the object as is if it's not that shape.
+    pub(crate) fn convert<'scale>(self, mut fields: Fields<'scale>) -> Value<'scale> {
+        let variant = match fields.get(1) {
+            Some((name, Value::Object(payload))) if fields.len() == 2 => {
+                match (self, name.as_ref(), &payload[1..]) {
+                    (Wrapper::Option, "None", []) => Some(None),
+                    (Wrapper::Option, "Some", [(field, _)])
+                    | (Wrapper::Result, "Ok", [(field, _)])
+                    | (Wrapper::Result, "Err", [(field, _)])
+                        if field == "0" =>
+                    {
+                        Some(Some(name.clone()))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        let name = match variant {
+            Some(None) => return Value::None,
+            Some(Some(name)) => name,
+            None => return Value::Object(Box::new(fields)),
+        };
+        let inner = match fields.pop() {
+            Some((_, Value::Object(mut payload))) => Box::new(payload.pop().unwrap().1),
+            _ => unreachable!(),
+        };
+        match &*name {
+            "Some" => Value::Some(inner),
+            "Ok" => Value::Ok(inner),
+            _ => Value::Err(inner),
+        }
+    }
 }
 
 impl<'scale> ValueBuilder<'scale> {
@@ -216,40 +491,24 @@ impl<'scale> ValueBuilder<'scale> {
     ) -> Value<'scale> {
         let mut slf = ValueBuilder::<'scale>::default();
         crate::skeleton_decode(data, top_type_id, &mut slf, types);
-        slf.root.take().unwrap()
+        slf.finish().unwrap()
     }
 
-    fn append(
-        path: &[(&'scale str, u32)],
-        current: &mut Value<'scale>,
-        new_field: &'scale str,
-        new_val: Value<'scale>,
-    ) {
-        if let Value::<'scale>::Object(fields) = current {
-            if path.is_empty() {
-                // println!("appending path {:?} fin {:?}  / {:?} to {:?}",path, new_field, new_val, &fields);
-                fields.push((new_field, new_val));
-                return;
-            }
-
-            let ((head, head_ty), tail) = path.split_first().unwrap();
-            for (field, child) in fields.iter_mut() {
-                if field == head {
-                    // println!("appending deeper new path {:?} | {:?}  / {:?} ", &tail, new_field, new_val);
-                    ValueBuilder::append(tail, child, new_field, new_val);
-                    return;
-                }
-            }
-            // println!("appending path {:?} notfound {:?} adding {:?} | {:?}  / {:?} ", &tail, head, fields, new_field, new_val);
-
-            fields.push((
-                head,
-                Value::Object(Box::new(vec![("_ty", Value::U32(*head_ty))])),
-            ));
-            let (_, new_current) = fields.last_mut().unwrap();
-            ValueBuilder::append(tail, new_current, new_field, new_val);
+    /// The built value, for when driving the builder's [`VisitScale`](crate::VisitScale)
+    /// events by hand. `None` if nothing was visited or an object wasn't left.
+    pub fn finish(self) -> Option<Value<'scale>> {
+        if self.open.is_empty() {
+            self.root
         } else {
-            panic!()
+            None
+        }
+    }
+
+    /// Add a finished value to the innermost open object (or make it the root).
+    fn place(&mut self, name: Cow<'scale, str>, val: Value<'scale>) {
+        match self.open.last_mut() {
+            Some((_, fields, _)) => fields.push((name, val)),
+            None => self.root = Some(val),
         }
     }
 
@@ -264,27 +523,41 @@ impl<'scale> ValueBuilder<'scale> {
     fn parse_bitvec(mut data: &'scale [u8]) -> Option<Value> {
         assert_eq!(data.len(), 1, "bitvec size not suppored - please send pr.");
         use parity_scale_codec::Decode;
-        Some(
-             Value::Bits(Box::new(
-                scale_value::BitSequence::decode(&mut data).unwrap())))
-                // <bitvec::prelude::BitVec<u8, bitvec::prelude::Lsb0>
-                // as
-                // parity_scale_codec::Decode>::decode(&mut data).unwrap())))
+        Some(Value::Bits(Box::new(
+            scale_value::BitSequence::decode(&mut data).unwrap(),
+        )))
+        // <bitvec::prelude::BitVec<u8, bitvec::prelude::Lsb0>
+        // as
+        // parity_scale_codec::Decode>::decode(&mut data).unwrap())))
+    }
+}
+
+/// The field name for the last segment of `path`: borrowed from the registry,
+/// or formatted for a position.
+fn field_name<'scale>(path: &[(PathSegment<'scale>, u32)]) -> Cow<'scale, str> {
+    match path.last() {
+        Some((segment, _)) => match segment.as_name() {
+            Some(name) => Cow::Borrowed(name),
+            None => Cow::Owned(segment.to_string()),
+        },
+        None => Cow::Borrowed(""),
     }
 }
 
 impl<'scale> super::VisitScale<'scale> for ValueBuilder<'scale> {
     fn visit(
         &mut self,
-        current_path: &[(&'scale str, u32)],
+        current_path: &[(PathSegment<'scale>, u32)],
         data: &'scale [u8],
         ty: &scale_info::Type<scale_info::form::PortableForm>,
         types: &PortableRegistry,
     ) {
         let new_val = match ty.type_def() {
-            scale_info::TypeDef::Primitive(TypeDefPrimitive::Str) => Some(Value::Str(
-                <&'scale str as crate::borrow_decode::BorrowDecode>::borrow_decode(data),
-            )),
+            scale_info::TypeDef::Primitive(TypeDefPrimitive::Str) => {
+                Some(Value::Str(Cow::Borrowed(
+                    <&'scale str as crate::borrow_decode::BorrowDecode>::borrow_decode(data),
+                )))
+            }
             scale_info::TypeDef::Primitive(TypeDefPrimitive::Bool) => Some(Value::Bool(
                 <bool as crate::borrow_decode::BorrowDecode>::borrow_decode(data),
             )),
@@ -332,29 +605,80 @@ impl<'scale> super::VisitScale<'scale> for ValueBuilder<'scale> {
             }
         };
 
-        // place val in right location.
-        let last = if self.root.is_none() {
-            if current_path.is_empty() {
-                self.root = new_val;
-                return;
-            }
-            let (last, last_ty) = current_path.last().unwrap();
-            self.root = Some(Value::Object(Box::new(vec![("_ty", Value::U32(*last_ty))])));
-            last
-        } else {
-            let (last, _) = current_path.last().unwrap();
-            last
+        if let Some(new_val) = new_val {
+            self.place(field_name(current_path), new_val);
+        }
+    }
+
+    fn enter(
+        &mut self,
+        path: &[(PathSegment<'scale>, u32)],
+        ty_id: u32,
+        ty: &'scale scale_info::Type<scale_info::form::PortableForm>,
+    ) {
+        let name = field_name(path);
+        // A variant is entered with the type of its enum straight after the
+        // enum itself; only the enum's object gets converted.
+        let wrapper = match self.open.last() {
+            Some((_, fields, w)) if *w != Wrapper::No && fields.len() == 1 => Wrapper::No,
+            _ => Wrapper::of(ty),
         };
+        self.open
+            .push((name, vec![("_ty".into(), Value::U32(ty_id))], wrapper));
+    }
 
-        // println!("appending {:?}  / {:?}", current_path, new_val);
+    fn leave(&mut self, _path: &[(PathSegment<'scale>, u32)]) {
+        let (name, fields, wrapper) = self.open.pop().expect("leave without enter");
+        self.place(name, wrapper.convert(fields));
+    }
+}
 
-        ValueBuilder::append(
-            &current_path[..current_path.len() - 1],
-            self.root.as_mut().unwrap(),
-            last,
-            new_val.unwrap(),
-        );
+/// Decimal representation of a little endian 256 bit unsigned number.
+#[cfg(any(feature = "display", feature = "serde"))]
+pub(crate) fn u256_to_string(le_bytes: &[u8; 32]) -> String {
+    // Big endian base 2^32 limbs, repeatedly divided by 10^9.
+    let mut limbs: Vec<u64> = le_bytes
+        .chunks(4)
+        .rev()
+        .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]) as u64)
+        .collect();
+    let mut chunks = vec![];
+    while limbs.iter().any(|l| *l != 0) {
+        let mut rem = 0u64;
+        for limb in limbs.iter_mut() {
+            let cur = (rem << 32) | *limb;
+            *limb = cur / 1_000_000_000;
+            rem = cur % 1_000_000_000;
+        }
+        chunks.push(rem);
+    }
+    match chunks.split_last() {
+        None => "0".into(),
+        Some((most_significant, rest)) => {
+            let mut out = most_significant.to_string();
+            for chunk in rest.iter().rev() {
+                out.push_str(&format!("{:09}", chunk));
+            }
+            out
+        }
+    }
+}
+
+/// Decimal representation of a little endian two's complement 256 bit number.
+#[cfg(any(feature = "display", feature = "serde"))]
+pub(crate) fn i256_to_string(le_bytes: &[u8; 32]) -> String {
+    if le_bytes[31] & 0x80 == 0 {
+        return u256_to_string(le_bytes);
+    }
+    // Negate: invert and add one.
+    let mut magnitude = [0u8; 32];
+    let mut carry = true;
+    for (out, b) in magnitude.iter_mut().zip(le_bytes) {
+        let (sum, overflow) = (!b).overflowing_add(carry as u8);
+        *out = sum;
+        carry = overflow;
     }
+    format!("-{}", u256_to_string(&magnitude))
 }
 
 #[cfg(test)]
@@ -363,7 +687,10 @@ mod tests {
 
     #[test]
     fn test_iter() {
-        let val = Value::Object(Box::new(vec![("0", Value::U32(0)), ("1", Value::U32(1))]));
+        let val = Value::Object(Box::new(vec![
+            ("0".into(), Value::U32(0)),
+            ("1".into(), Value::U32(1)),
+        ]));
 
         let it = val.into_iter();
         for i in it {
@@ -376,8 +703,8 @@ mod tests {
     fn test_display() {
         let data = &[1, 2, 3, 4, 17, 18, 19, 20];
         let val = Value::Object(Box::new(vec![
-            ("0", Value::U32(0)),
-            ("1", Value::Scale(data)),
+            ("0".into(), Value::U32(0)),
+            ("1".into(), Value::Scale(data)),
         ]));
 
         assert_eq!(
@@ -387,8 +714,8 @@ mod tests {
 
         let data = &[7; 200];
         let val = Value::Object(Box::new(vec![
-            ("0", Value::U32(0)),
-            ("1", Value::Scale(data)),
+            ("0".into(), Value::U32(0)),
+            ("1".into(), Value::Scale(data)),
         ]));
 
         assert_eq!(
@@ -0,0 +1,215 @@
+//! Depth first traversal of a [`Value`] tree.
+//!
+//! [`Value::walk`] calls a [`ValueVisitor`] before and after each value with
+//! its path from the root; [`Value::leaves`] iterates over just the leaves.
+//! Both skip the synthetic `_ty` fields and step through `Some`, `Ok` and
+//! `Err` as if they were a field of that name, the same as [`Value::get`].
+//!
+//! ```
+//! # use scale_borrow::ValueBuilder;
+//! # let mut registry = scale_info::Registry::new();
+//! # let ty = registry.register_type(&scale_info::MetaType::new::<(bool, u8)>()).id();
+//! # let registry: scale_info::PortableRegistry = registry.into();
+//! # let val = ValueBuilder::parse(&[1, 2], ty, &registry);
+//! // Flatten to `path = value` lines.
+//! for (path, leaf) in val.leaves() {
+//!     println!("{} = {:?}", path, leaf);
+//! }
+//! ```
+use crate::Value;
+
+/// Callbacks for [`Value::walk`]. `path` holds the field names from the root
+/// to `value` (empty for the root itself).
+pub trait ValueVisitor<'a, 'scale> {
+    /// Called before visiting the children of `value`. Return `false` to
+    /// skip them (`post` is still called).
+    fn pre(&mut self, _path: &[&'a str], _value: &'a Value<'scale>) -> bool {
+        true
+    }
+
+    /// Called once all the children of `value` have been visited.
+    fn post(&mut self, _path: &[&'a str], _value: &'a Value<'scale>) {}
+}
+
+impl<'scale> Value<'scale> {
+    /// The fields of an object (without `_ty`) or the value in a `Some`, `Ok`
+    /// or `Err`, with their names. Nothing for other values.
+    pub(crate) fn children<'a>(&'a self) -> impl Iterator<Item = (&'a str, &'a Value<'scale>)> {
+        let fields = match self {
+            Value::Object(fields) => fields.as_slice(),
+            _ => &[],
+        };
+        let wrapped = match self {
+            Value::Some(inner) | Value::Ok(inner) | Value::Err(inner) => {
+                self.wrapper().zip(Some(&**inner))
+            }
+            _ => None,
+        };
+        fields
+            .iter()
+            .filter(|(name, _)| name != "_ty")
+            .map(|(name, val)| (name.as_ref(), val))
+            .chain(wrapped)
+    }
+
+    /// Visit every value in the tree, depth first in document order.
+    pub fn walk<'a, V: ValueVisitor<'a, 'scale>>(&'a self, visitor: &mut V) {
+        fn walk_at<'a, 'scale, V: ValueVisitor<'a, 'scale>>(
+            val: &'a Value<'scale>,
+            path: &mut Vec<&'a str>,
+            visitor: &mut V,
+        ) {
+            if visitor.pre(path, val) {
+                for (name, child) in val.children() {
+                    path.push(name);
+                    walk_at(child, path, visitor);
+                    path.pop();
+                }
+            }
+            visitor.post(path, val);
+        }
+        walk_at(self, &mut vec![], visitor);
+    }
+
+    /// Every leaf (anything that isn't an object, `Some`, `Ok` or `Err`) with
+    /// its dotted path, as used by [`Value::get`], in document order.
+    pub fn leaves(&self) -> Leaves<'_, 'scale> {
+        Leaves {
+            stack: vec![(String::new(), self)],
+        }
+    }
+}
+
+/// Iterator returned by [`Value::leaves`].
+pub struct Leaves<'a, 'scale> {
+    /// Values still to visit, next one last.
+    stack: Vec<(String, &'a Value<'scale>)>,
+}
+
+impl<'a, 'scale> Iterator for Leaves<'a, 'scale> {
+    type Item = (String, &'a Value<'scale>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (path, val) = self.stack.pop()?;
+            match val {
+                Value::Object(_) | Value::Some(_) | Value::Ok(_) | Value::Err(_) => {
+                    let children: Vec<_> = val.children().collect();
+                    for (name, child) in children.into_iter().rev() {
+                        let child_path = if path.is_empty() {
+                            name.to_string()
+                        } else {
+                            format!("{}.{}", path, name)
+                        };
+                        self.stack.push((child_path, child));
+                    }
+                }
+                leaf => return Some((path, leaf)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::make_type_id;
+    use crate::ValueBuilder;
+    use parity_scale_codec::Encode;
+
+    #[derive(Encode, scale_info::TypeInfo)]
+    enum Kind {
+        #[allow(dead_code)]
+        Plain,
+        Tagged { tag: u8 },
+    }
+
+    #[derive(Encode, scale_info::TypeInfo)]
+    struct Item {
+        id: u32,
+        kind: Kind,
+        parent: Option<u32>,
+        children: Vec<u16>,
+    }
+
+    fn item() -> Value<'static> {
+        let encoded = Item {
+            id: 1,
+            kind: Kind::Tagged { tag: 9 },
+            parent: Some(4),
+            children: vec![5, 6],
+        }
+        .encode();
+        let (id, types) = make_type_id::<Item>();
+        ValueBuilder::parse(&encoded, id, &types).into_owned()
+    }
+
+    #[test]
+    fn leaves() {
+        let val = item();
+        let leaves: Vec<_> = val.leaves().collect();
+        assert_eq!(
+            leaves,
+            vec![
+                ("id".to_string(), &Value::U32(1)),
+                ("kind.Tagged.tag".to_string(), &Value::U8(9)),
+                ("parent.Some".to_string(), &Value::U32(4)),
+                ("children.0".to_string(), &Value::U16(5)),
+                ("children.1".to_string(), &Value::U16(6)),
+            ]
+        );
+        for (path, leaf) in leaves {
+            assert_eq!(val.get(&path), Some(leaf));
+        }
+
+        let root = Value::U8(3);
+        assert_eq!(
+            root.leaves().collect::<Vec<_>>(),
+            vec![(String::new(), &root)]
+        );
+        let empty = Value::Object(Box::default());
+        assert_eq!(empty.leaves().count(), 0);
+        // Used to hit a todo!().
+        assert_eq!(root.into_iter().count(), 0);
+    }
+
+    #[test]
+    fn pre_and_post() {
+        #[derive(Default)]
+        struct Events(Vec<String>);
+        impl<'a, 'scale> ValueVisitor<'a, 'scale> for Events {
+            fn pre(&mut self, path: &[&'a str], _value: &'a Value<'scale>) -> bool {
+                self.0.push(format!("pre {}", path.join(".")));
+                path.last() != Some(&"kind")
+            }
+            fn post(&mut self, path: &[&'a str], _value: &'a Value<'scale>) {
+                self.0.push(format!("post {}", path.join(".")));
+            }
+        }
+
+        let val = item();
+        let mut events = Events::default();
+        val.walk(&mut events);
+        assert_eq!(
+            events.0,
+            vec![
+                "pre ",
+                "pre id",
+                "post id",
+                "pre kind",
+                "post kind",
+                "pre parent",
+                "pre parent.Some",
+                "post parent.Some",
+                "post parent",
+                "pre children",
+                "pre children.0",
+                "post children.0",
+                "pre children.1",
+                "post children.1",
+                "post children",
+                "post ",
+            ]
+        );
+    }
+}